@@ -0,0 +1,45 @@
+const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// The bracket right after or right before `offset` and the one that pairs
+/// with it, as byte offsets. Nested pairs of the same kind are skipped.
+pub fn find_matching_bracket(text: &str, offset: usize) -> Option<(usize, usize)> {
+    let after = text[offset..].chars().next().map(|c| (offset, c));
+    let before = text[..offset].char_indices().next_back();
+
+    [after, before]
+        .into_iter()
+        .flatten()
+        .find_map(|(at, c)| matching(text, at, c).map(|other| (at, other)))
+}
+
+fn matching(text: &str, at: usize, c: char) -> Option<usize> {
+    let mut depth = 0;
+
+    if let Some(&(open, close)) = PAIRS.iter().find(|(open, _)| *open == c) {
+        for (index, c) in text[at..].char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(at + index);
+                }
+            }
+        }
+    } else if let Some(&(open, close)) = PAIRS.iter().find(|(_, close)| *close == c) {
+        for (index, c) in text[..=at].char_indices().rev() {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+        }
+    }
+
+    None
+}
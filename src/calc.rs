@@ -0,0 +1,172 @@
+/// Evaluates an arithmetic expression with `+ - * / %`, parentheses,
+/// decimal numbers and `0x` hex integers.
+pub fn evaluate(expression: &str) -> Result<f64, &'static str> {
+    let mut parser = Parser { input: expression.as_bytes(), position: 0, depth: 0 };
+
+    let value = parser.sum()?;
+    parser.skip_whitespace();
+
+    if parser.position < parser.input.len() {
+        return Err("Не удалось разобрать выражение");
+    }
+
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err("Результат слишком велик")
+    }
+}
+
+/// Prints `value` without float noise: whole numbers without a fraction
+/// and everything else rounded to ten places with trailing zeros dropped.
+pub fn format(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+
+    let formatted = format!("{value:.10}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    match trimmed {
+        "-0" => String::from("0"),
+        trimmed => trimmed.to_owned()
+    }
+}
+
+/// How deep parentheses and signs may nest. Each level is a few stack
+/// frames, so this keeps a pasted wall of `(` from overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize
+}
+
+impl Parser<'_> {
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<f64, &'static str>) -> Result<f64, &'static str> {
+        if self.depth == MAX_DEPTH {
+            return Err("Слишком глубокая вложенность");
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.position).map_or(false, u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn next_is(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+
+        if self.input.get(self.position) == Some(&byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<f64, &'static str> {
+        let mut value = self.product()?;
+
+        loop {
+            if self.next_is(b'+') {
+                value += self.product()?;
+            } else if self.next_is(b'-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, &'static str> {
+        let mut value = self.unary()?;
+
+        loop {
+            let operator = [b'*', b'/', b'%'].into_iter().find(|&operator| self.next_is(operator));
+
+            let Some(operator) = operator else { return Ok(value) };
+            let right = self.unary()?;
+
+            if operator != b'*' && right == 0.0 {
+                return Err("Деление на ноль");
+            }
+
+            value = match operator {
+                b'*' => value * right,
+                b'/' => value / right,
+                _ => value % right
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, &'static str> {
+        if self.next_is(b'-') {
+            Ok(-self.nested(Self::unary)?)
+        } else if self.next_is(b'+') {
+            self.nested(Self::unary)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<f64, &'static str> {
+        if self.next_is(b'(') {
+            let value = self.nested(Self::sum)?;
+
+            return if self.next_is(b')') { Ok(value) } else { Err("Не хватает закрывающей скобки") };
+        }
+
+        self.skip_whitespace();
+
+        let rest = &self.input[self.position..];
+
+        if let Some(hex) = rest.strip_prefix(b"0x").or_else(|| rest.strip_prefix(b"0X")) {
+            let length = hex.iter().take_while(|byte| byte.is_ascii_hexdigit()).count();
+            let digits = std::str::from_utf8(&hex[..length]).map_err(|_| "Неверное число")?;
+            let value = u64::from_str_radix(digits, 16).map_err(|_| "Неверное шестнадцатеричное число")?;
+
+            self.position += 2 + length;
+            return Ok(value as f64);
+        }
+
+        let length = rest.iter().take_while(|byte| byte.is_ascii_digit() || **byte == b'.').count();
+
+        // An exponent like `1e-3` belongs to the number.
+        let length = match rest.get(length) {
+            Some(b'e' | b'E') if length > 0 => {
+                let sign = usize::from(matches!(rest.get(length + 1), Some(b'+' | b'-')));
+                let digits = rest[length + 1 + sign..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+
+                if digits > 0 { length + 1 + sign + digits } else { length }
+            },
+            _ => length
+        };
+
+        let number = std::str::from_utf8(&rest[..length]).map_err(|_| "Неверное число")?;
+        let value = number.parse::<f64>().map_err(|_| "Не удалось разобрать выражение")?;
+
+        self.position += length;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_nesting_is_an_error_rather_than_a_crash() {
+        assert_eq!(evaluate(&"(".repeat(100_000)), Err("Слишком глубокая вложенность"));
+        assert_eq!(evaluate(&"-".repeat(100_000)), Err("Слишком глубокая вложенность"));
+        assert_eq!(evaluate(&format!("{}1{}", "(".repeat(200), ")".repeat(200))), Ok(1.0));
+    }
+}
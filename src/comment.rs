@@ -0,0 +1,100 @@
+/// The line comment token for a file extension, as used for highlighting.
+pub fn line_token(extension: &str) -> Option<&'static str> {
+    match extension {
+        "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "java" | "js" | "ts" | "go" | "cs" | "swift" | "kt"
+            | "jsx" | "tsx" | "scss" | "php" | "dart" | "scala" | "proto" => Some("//"),
+        "py" | "sh" | "bash" | "rb" | "pl" | "toml" | "yaml" | "yml" | "r"
+            | "zsh" | "fish" | "ps1" | "conf" | "cfg" | "mk" | "cmake" | "nix" => Some("#"),
+        "sql" | "lua" | "hs" | "elm" | "ada" => Some("--"),
+        "ini" | "asm" | "lisp" | "clj" => Some(";"),
+        _ => None
+    }
+}
+
+/// The block comment delimiters for a file extension.
+pub fn block_tokens(extension: &str) -> Option<(&'static str, &'static str)> {
+    match extension {
+        "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "java" | "js" | "ts" | "go" | "cs" | "swift" | "kt"
+            | "jsx" | "tsx" | "css" | "scss" | "less" | "php" | "dart" | "scala" | "proto" | "sql" => Some(("/*", "*/")),
+        "html" | "htm" | "xml" | "svg" | "vue" | "md" => Some(("<!--", "-->")),
+        "hs" | "elm" => Some(("{-", "-}")),
+        "ml" | "pas" => Some(("(*", "*)")),
+        _ => None
+    }
+}
+
+/// Whether `offset` falls inside a block comment that opens before it and
+/// isn't closed yet.
+pub fn is_inside_block(text: &str, offset: usize, (open, close): (&str, &str)) -> bool {
+    text[..offset]
+        .rfind(open)
+        .map_or(false, |start| !text[start + open.len()..offset].contains(close))
+}
+
+/// Comments out every non-blank line after its indentation, or uncomments
+/// them all if each one already starts with `token`.
+pub fn toggle_lines(lines: &mut [String], token: &str) {
+    let is_commented = |line: &String| line.trim_start().starts_with(token);
+    let is_blank = |line: &String| line.trim().is_empty();
+
+    let uncomment = lines.iter().filter(|line| !is_blank(line)).all(is_commented);
+
+    for line in lines.iter_mut().filter(|line| !is_blank(line)) {
+        let indent = line.len() - line.trim_start().len();
+
+        if uncomment {
+            let rest = &line[indent + token.len()..];
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+
+            *line = format!("{}{}", &line[..indent], rest);
+        } else {
+            line.insert_str(indent, &format!("{token} "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toggled(lines: &[&str], token: &str) -> Vec<String> {
+        let mut lines = lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        toggle_lines(&mut lines, token);
+        lines
+    }
+
+    #[test]
+    fn comments_each_line_after_its_own_indentation() {
+        assert_eq!(
+            toggled(&["fn main() {", "    let a = 1;", "\tb();", "}"], "//"),
+            ["// fn main() {", "    // let a = 1;", "\t// b();", "// }"]
+        );
+    }
+
+    #[test]
+    fn uncomments_when_every_line_is_commented() {
+        assert_eq!(
+            toggled(&["# a", "    #b", "\t# c"], "#"),
+            ["a", "    b", "\tc"]
+        );
+    }
+
+    #[test]
+    fn comments_everything_when_only_some_lines_are_commented() {
+        assert_eq!(
+            toggled(&["// a", "b", "  // c"], "//"),
+            ["// // a", "// b", "  // // c"]
+        );
+    }
+
+    #[test]
+    fn leaves_blank_lines_alone() {
+        assert_eq!(toggled(&["a", "", "   ", "b"], "--"), ["-- a", "", "   ", "-- b"]);
+        assert_eq!(toggled(&["-- a", "", "   ", "-- b"], "--"), ["a", "", "   ", "b"]);
+    }
+
+    #[test]
+    fn a_selection_of_only_blank_lines_is_unchanged() {
+        assert_eq!(toggled(&["", "  "], "#"), ["", "  "]);
+    }
+}
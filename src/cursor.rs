@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use iced::widget::text_editor::{Action, Content, Motion};
+
+pub fn offset(text: &str, (line, column): (usize, usize)) -> usize {
+    let start = text
+        .split('\n')
+        .take(line)
+        .map(|line| line.len() + 1)
+        .sum::<usize>()
+        .min(text.len());
+
+    let length = text[start..].find('\n').unwrap_or(text.len() - start);
+    let mut offset = start + column.min(length);
+
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    offset
+}
+
+/// The line and column of a byte offset, the inverse of `offset`.
+pub fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+    (before.matches('\n').count(), offset - start)
+}
+
+pub fn cursor_offset(content: &Content, text: &str) -> usize {
+    offset(text, content.cursor_position())
+}
+
+/// The byte range of the selection. `Content` only reports the selected text
+/// and the cursor, which sits at one end or the other.
+pub fn selection_range(content: &Content, text: &str) -> Option<Range<usize>> {
+    let selection = content.selection()?;
+    let cursor = cursor_offset(content, text);
+    let length = selection.len();
+
+    if cursor >= length && text.get(cursor - length..cursor) == Some(selection.as_str()) {
+        Some(cursor - length..cursor)
+    } else if text.get(cursor..cursor + length) == Some(selection.as_str()) {
+        Some(cursor..cursor + length)
+    } else {
+        None
+    }
+}
+
+pub fn move_to(content: &mut Content, text: &str, offset: usize) {
+    content.edit(Action::Move(Motion::DocumentStart));
+
+    for _ in text[..offset].chars() {
+        content.edit(Action::Move(Motion::Right));
+    }
+}
+
+pub fn select(content: &mut Content, text: &str, range: Range<usize>) {
+    move_to(content, text, range.start);
+
+    for _ in text[range].chars() {
+        content.edit(Action::Select(Motion::Right));
+    }
+}
+
+/// The word around `offset`, if the offset touches one.
+pub fn word_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(offset, |(index, _)| index);
+
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, c)| !is_word(c))
+        .map_or(text.len(), |(index, _)| offset + index);
+
+    (start < end).then_some(start..end)
+}
@@ -0,0 +1,20 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, Utc};
+
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// The current time in `format`, with the usual `strftime` specifiers, or
+/// `None` when the format doesn't parse.
+pub fn now(format: &str, utc: bool) -> Option<String> {
+    let items = StrftimeItems::new(format).collect::<Vec<_>>();
+
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return None;
+    }
+
+    Some(if utc {
+        Utc::now().format_with_items(items.iter()).to_string()
+    } else {
+        Local::now().format_with_items(items.iter()).to_string()
+    })
+}
@@ -0,0 +1,1257 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use iced::widget::text_editor;
+
+use crate::brackets;
+use crate::calc;
+use crate::comment;
+use crate::cursor;
+use crate::file::{Error, Format, Loaded, SaveOptions, Saved, Stamp};
+use crate::history::History;
+use crate::line_ending::LineEnding;
+use crate::lines::{self, Case, Direction, Sort};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub words: usize,
+    pub chars: usize
+}
+
+impl Counts {
+    fn of(text: &str) -> Self {
+        Counts {
+            words: text.split_whitespace().count(),
+            chars: text.chars().count()
+        }
+    }
+}
+
+pub struct Document {
+    pub id: usize,
+    pub content: text_editor::Content,
+    pub error: Option<Error>,
+    pub notice: Option<String>,
+    pub path: Option<PathBuf>,
+    pub revision: u64,
+    saved_revision: u64,
+    saved_fingerprint: u64,
+    // Revision and fingerprint of the text handed to the save in flight.
+    pub saving: Option<(u64, u64)>,
+    pub history: History,
+    pub stamp: Option<Stamp>,
+    pub read_only: bool,
+    pub counts: Counts,
+    pub format: Format,
+    pub lossy: bool,
+    pub line_ending: LineEnding,
+    pub mixed_endings: bool,
+    pub syntax_override: Option<String>,
+    pub binary: bool,
+    pub partial: bool,
+    // Something else wrote the file since it was loaded or saved.
+    pub changed_on_disk: bool,
+    // Extra cursors besides the one `Content` has, as byte ranges that are
+    // empty or hold the same text as the main selection.
+    pub cursors: Vec<Range<usize>>
+}
+
+impl Document {
+    pub fn new(id: usize, path: Option<PathBuf>, line_ending: LineEnding) -> Self {
+        let mut document = Document {
+            id,
+            content: text_editor::Content::with(""),
+            error: None,
+            notice: None,
+            path,
+            revision: 0,
+            saved_revision: 0,
+            saved_fingerprint: 0,
+            saving: None,
+            history: History::default(),
+            stamp: None,
+            read_only: false,
+            counts: Counts::default(),
+            format: Format::default(),
+            lossy: false,
+            line_ending,
+            mixed_endings: false,
+            syntax_override: None,
+            binary: false,
+            partial: false,
+            changed_on_disk: false,
+            cursors: Vec::new()
+        };
+
+        document.mark_clean();
+        document
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .as_deref()
+            .and_then(|path| path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("Untitled"))
+    }
+
+    pub fn syntax(&self) -> &str {
+        self.syntax_override
+            .as_deref()
+            .or_else(|| self.path.as_ref()?.extension()?.to_str())
+            .unwrap_or("rs")
+    }
+
+    /// A document nobody has typed into or loaded anything into yet, which
+    /// can be reused instead of opening another tab.
+    pub fn is_placeholder(&self) -> bool {
+        self.stamp.is_none() && !self.is_dirty() && self.content.text().is_empty()
+    }
+
+    pub fn load(&mut self, loaded: Loaded) {
+        self.path = Some(loaded.path);
+        self.stamp = loaded.stamp;
+        self.read_only = loaded.read_only;
+        self.format = loaded.format;
+        self.lossy = loaded.lossy;
+        self.line_ending = loaded.line_ending;
+        self.mixed_endings = loaded.mixed_endings;
+        self.binary = loaded.binary;
+        self.partial = loaded.partial;
+        self.changed_on_disk = false;
+
+        let (line, column) = self.content.cursor_position();
+        self.content = text_editor::Content::with(loaded.text.as_str());
+        self.cursors.clear();
+        self.restore_cursor(line, column);
+
+        self.error = None;
+        self.saving = None;
+        self.history.clear();
+        self.counts = Counts::of(&loaded.text);
+
+        // New text is a new revision even though it is clean, so nothing
+        // found in the old one is taken for it.
+        self.revision += 1;
+        self.mark_clean();
+    }
+
+    /// Fills an unnamed document with text that didn't come from a file. It
+    /// counts as unsaved, so the first save asks where to put it.
+    pub fn load_piped(&mut self, text: String, line_ending: LineEnding) {
+        self.content = text_editor::Content::with(&text);
+        self.line_ending = line_ending;
+        self.counts = Counts::of(&text);
+        self.touch();
+    }
+
+    pub fn saved(&mut self, saved: Saved) {
+        self.path = Some(saved.path);
+        self.stamp = saved.stamp;
+        self.read_only = false;
+        self.lossy = false;
+        self.mixed_endings = false;
+        self.changed_on_disk = false;
+        self.error = saved.warning;
+
+        // Edits made while the save was in flight are not on disk yet, so
+        // only what was handed to the save counts as clean.
+        match self.saving.take() {
+            Some((revision, fingerprint)) => {
+                self.saved_revision = revision;
+                self.saved_fingerprint = fingerprint;
+            },
+            None => self.mark_clean()
+        }
+    }
+
+    pub fn begin_save(&mut self) {
+        // The written file never has mixed endings, whatever the buffer says.
+        self.saving = Some((self.revision, self.fingerprint(false)));
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        if self.format != format {
+            self.format = format;
+            self.touch();
+        }
+    }
+
+    pub fn set_bom(&mut self, bom: bool) {
+        if self.format.supports_bom() && self.format.bom != bom {
+            self.format.bom = bom;
+            self.touch();
+        }
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending || self.mixed_endings {
+            self.line_ending = line_ending;
+            self.mixed_endings = false;
+            self.touch();
+        }
+    }
+
+    /// Rewrites any stray `\r` left in the buffer (say, from a paste) and
+    /// settles the whole file on its dominant ending.
+    pub fn normalize_line_endings(&mut self) {
+        let text = self.content.text();
+        let normalized = LineEnding::normalize(&text);
+
+        if normalized != text {
+            self.set_text(normalized);
+        }
+
+        if self.mixed_endings {
+            self.mixed_endings = false;
+            self.touch();
+        }
+    }
+
+    pub fn edit(&mut self, action: text_editor::Action) {
+        if self.read_only && action.is_edit() {
+            return;
+        }
+
+        if !self.cursors.is_empty() {
+            if action.is_edit() && self.edit_cursors(&action) {
+                return;
+            }
+
+            if !matches!(action, text_editor::Action::Scroll { .. }) {
+                self.cursors.clear();
+            }
+        }
+
+        let is_edit = action.is_edit();
+
+        if is_edit {
+            let is_typing = matches!(
+                action,
+                text_editor::Action::Edit(text_editor::Edit::Insert(c)) if !c.is_whitespace()
+            );
+            self.history.record(self.content.text(), is_typing);
+        } else {
+            self.history.interrupt();
+        }
+
+        self.content.edit(action);
+
+        if is_edit {
+            self.notice = None;
+            self.touch();
+            self.counts = Counts::of(&self.content.text());
+        }
+    }
+
+    /// Breaks the line and carries its indentation over, one `unit` deeper
+    /// after an opening bracket, with a closer right after the cursor moved
+    /// down to a line of its own. A line holding only whitespace is emptied
+    /// first, so pressing Enter repeatedly doesn't leave indentation behind.
+    pub fn enter(&mut self, unit: &str) {
+        if self.read_only {
+            return;
+        }
+
+        if self.content.selection().is_some() || !self.cursors.is_empty() {
+            self.edit(text_editor::Action::Edit(text_editor::Edit::Enter));
+            return;
+        }
+
+        let text = self.content.text();
+        let (current, before) = self.cursor_line(&text);
+
+        let blank = !current.is_empty() && current.trim().is_empty();
+        let base = if blank { current } else { &before[..before.len() - before.trim_start().len()] }.to_owned();
+        let mut indent = base.clone();
+
+        let opener = before.trim_end().chars().last();
+        let opens_block = match opener {
+            Some('{' | '(' | '[') => true,
+            Some(':') => self.syntax() == "py",
+            _ => false
+        };
+
+        let closer = match opener {
+            Some('{') => Some('}'),
+            Some('(') => Some(')'),
+            Some('[') => Some(']'),
+            _ => None
+        };
+        let splits_pair = closer.is_some() && current[before.len()..].trim_start().chars().next() == closer;
+
+        if opens_block {
+            indent.push_str(unit);
+        }
+
+        self.history.record(text, false);
+
+        if blank {
+            self.content.edit(text_editor::Action::Move(text_editor::Motion::Home));
+            self.content.edit(text_editor::Action::Select(text_editor::Motion::End));
+            self.content.edit(text_editor::Action::Edit(text_editor::Edit::Delete));
+        }
+
+        self.content.edit(text_editor::Action::Edit(text_editor::Edit::Enter));
+
+        if !indent.is_empty() {
+            self.content.edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(indent))));
+        }
+
+        if splits_pair {
+            // The indentation is plain whitespace, so columns are characters.
+            let (_, column) = self.content.cursor_position();
+
+            self.content.edit(text_editor::Action::Edit(text_editor::Edit::Enter));
+
+            if !base.is_empty() {
+                self.content.edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(base))));
+            }
+
+            self.content.edit(text_editor::Action::Move(text_editor::Motion::Up));
+            self.content.edit(text_editor::Action::Move(text_editor::Motion::Home));
+
+            for _ in 0..column {
+                self.content.edit(text_editor::Action::Move(text_editor::Motion::Right));
+            }
+        }
+
+        self.notice = None;
+        self.touch();
+        self.counts = Counts::of(&self.content.text());
+    }
+
+    /// Types `c`, adding the closing half when it opens a pair, or stepping
+    /// over a closing half that is already there. Pairs are only opened in
+    /// front of whitespace or a closer, and quotes not right after a word,
+    /// so `don't` stays as typed.
+    pub fn insert_closing(&mut self, c: char) {
+        const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\''), ('`', '`')];
+
+        let insert = text_editor::Action::Edit(text_editor::Edit::Insert(c));
+
+        if self.read_only || !self.cursors.is_empty() || self.content.selection().is_some() {
+            self.edit(insert);
+            return;
+        }
+
+        let text = self.content.text();
+        let offset = cursor::cursor_offset(&self.content, &text);
+        let next = text[offset..].chars().next();
+        let previous = text[..offset].chars().next_back();
+
+        if next == Some(c) && PAIRS.iter().any(|&(_, close)| close == c) {
+            self.edit(text_editor::Action::Move(text_editor::Motion::Right));
+            return;
+        }
+
+        let opens = next.map_or(true, |next| next.is_whitespace() || PAIRS.iter().any(|&(open, close)| open != close && close == next));
+
+        match PAIRS.iter().find(|&&(open, _)| open == c) {
+            Some(&(open, close)) if opens && (open != close || !previous.map_or(false, char::is_alphanumeric)) => {
+                self.edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(format!("{open}{close}")))));
+                self.content.edit(text_editor::Action::Move(text_editor::Motion::Left));
+            },
+            _ => self.edit(insert)
+        }
+    }
+
+    /// Types spaces up to the next indent stop instead of a tab.
+    pub fn insert_soft_tab(&mut self, width: usize) {
+        let width = width.max(1);
+        let text = self.content.text();
+        let (_, before) = self.cursor_line(&text);
+        let spaces = width - lines::columns(before, width) % width;
+
+        self.edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(" ".repeat(spaces)))));
+    }
+
+    /// Backspace that, inside leading spaces, deletes back to the previous
+    /// indent stop as one step.
+    pub fn backspace(&mut self, width: usize) {
+        let backspace = text_editor::Action::Edit(text_editor::Edit::Backspace);
+        let text = self.content.text();
+        let (_, before) = self.cursor_line(&text);
+
+        if self.read_only
+            || self.content.selection().is_some()
+            || !self.cursors.is_empty()
+            || before.is_empty()
+            || !before.chars().all(|c| c == ' ')
+        {
+            self.edit(backspace);
+            return;
+        }
+
+        let count = (before.len() - 1) % width.max(1) + 1;
+
+        self.history.record(text, false);
+
+        for _ in 0..count {
+            self.content.edit(backspace.clone());
+        }
+
+        self.notice = None;
+        self.touch();
+        self.counts = Counts::of(&self.content.text());
+    }
+
+    /// The cursor line and the part of it before the cursor.
+    fn cursor_line<'a>(&self, text: &'a str) -> (&'a str, &'a str) {
+        let (line, column) = self.content.cursor_position();
+        let current = text.split('\n').nth(line).unwrap_or_default();
+        let mut end = column.min(current.len());
+
+        while !current.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        (current, &current[..end])
+    }
+
+    /// Puts the cursor in front of the bracket that pairs with the one next
+    /// to it. Does nothing away from brackets or when the pair is unclosed.
+    pub fn jump_to_matching_bracket(&mut self) {
+        let text = self.content.text();
+
+        if let Some((_, matching)) = brackets::find_matching_bracket(&text, cursor::cursor_offset(&self.content, &text)) {
+            cursor::move_to(&mut self.content, &text, matching);
+        }
+    }
+
+    /// Moves to the start of the visual line first when wrapping splits it,
+    /// then between the first non-blank character and column 0, extending
+    /// the selection if `select` is set.
+    pub fn smart_home(&mut self, select: bool) {
+        let motion = |motion| if select { text_editor::Action::Select(motion) } else { text_editor::Action::Move(motion) };
+
+        let text = self.content.text();
+        let (line, before) = self.cursor_line(&text);
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+
+        self.edit(motion(text_editor::Motion::Home));
+
+        let home = self.content.cursor_position().1.min(before.len());
+
+        if home > 0 && home < before.len() {
+            return;
+        }
+
+        let target = if before.len() == indent || indent == line.len() { 0 } else { indent };
+
+        if target < home {
+            for _ in line[target..home].chars() {
+                self.content.edit(motion(text_editor::Motion::Left));
+            }
+        } else {
+            for _ in line[home..target].chars() {
+                self.content.edit(motion(text_editor::Motion::Right));
+            }
+        }
+    }
+
+    /// Selects the word under the cursor or, with a selection, adds a cursor
+    /// on its next occurrence after the last one. Returns how many cursors
+    /// there are now.
+    pub fn add_next_occurrence(&mut self) -> usize {
+        let text = self.content.text();
+
+        let Some(primary) = cursor::selection_range(&self.content, &text) else {
+            if let Some(word) = cursor::word_at(&text, cursor::cursor_offset(&self.content, &text)) {
+                cursor::select(&mut self.content, &text, word);
+            }
+
+            return 1;
+        };
+
+        let needle = &text[primary.clone()];
+        let from = self.cursors.last().map_or(primary.end, |range| range.end);
+
+        let found = text[from..]
+            .find(needle)
+            .map(|start| from + start)
+            .or_else(|| text.find(needle))
+            .map(|start| start..start + needle.len());
+
+        if let Some(range) = found {
+            if range != primary && !self.cursors.contains(&range) {
+                self.cursors.push(range);
+            }
+        }
+
+        self.cursors.len() + 1
+    }
+
+    /// Applies a typing action at the main cursor and every extra one at
+    /// once. Returns `false` for edits only the main cursor can do.
+    fn edit_cursors(&mut self, action: &text_editor::Action) -> bool {
+        enum Change {
+            Insert(String),
+            Backspace,
+            Delete
+        }
+
+        let change = match action {
+            text_editor::Action::Edit(text_editor::Edit::Insert(c)) => Change::Insert(c.to_string()),
+            text_editor::Action::Edit(text_editor::Edit::Paste(pasted)) => Change::Insert(pasted.to_string()),
+            text_editor::Action::Edit(text_editor::Edit::Enter) => Change::Insert(String::from("\n")),
+            text_editor::Action::Edit(text_editor::Edit::Backspace) => Change::Backspace,
+            text_editor::Action::Edit(text_editor::Edit::Delete) => Change::Delete,
+            _ => return false
+        };
+
+        let text = self.content.text();
+        let primary = cursor::selection_range(&self.content, &text).unwrap_or_else(|| {
+            let offset = cursor::cursor_offset(&self.content, &text);
+            offset..offset
+        });
+
+        let mut ranges = self.cursors.clone();
+        ranges.push(primary.clone());
+        ranges.sort_by_key(|range| range.start);
+
+        let mut edited = String::with_capacity(text.len());
+        let mut carets = Vec::new();
+        let mut main_caret = 0;
+        let mut end = 0;
+
+        for range in ranges {
+            let is_primary = range == primary;
+
+            let removed = match change {
+                _ if !range.is_empty() => range,
+                Change::Insert(_) => range,
+                Change::Backspace => {
+                    let previous = text[..range.start].chars().next_back().map_or(0, char::len_utf8);
+                    range.start - previous..range.start
+                },
+                Change::Delete => {
+                    let next = text[range.start..].chars().next().map_or(0, char::len_utf8);
+                    range.start..range.start + next
+                }
+            };
+
+            // Cursors that ran into each other merge.
+            if removed.start < end {
+                continue;
+            }
+
+            edited.push_str(&text[end..removed.start]);
+
+            if let Change::Insert(inserted) = &change {
+                edited.push_str(inserted);
+            }
+
+            if is_primary {
+                main_caret = edited.len();
+            } else {
+                carets.push(edited.len()..edited.len());
+            }
+
+            end = removed.end;
+        }
+
+        edited.push_str(&text[end..]);
+
+        let is_typing = matches!(&change, Change::Insert(inserted) if !inserted.chars().any(char::is_whitespace));
+        self.history.record(text, is_typing);
+
+        self.content = text_editor::Content::with(&edited);
+        cursor::move_to(&mut self.content, &edited, main_caret);
+        self.cursors = carets;
+
+        self.notice = None;
+        self.touch();
+        self.counts = Counts::of(&edited);
+
+        true
+    }
+
+    pub fn set_text(&mut self, text: String) {
+        if self.read_only {
+            return;
+        }
+
+        self.history.record(self.content.text(), false);
+        self.content = text_editor::Content::with(&text);
+        self.cursors.clear();
+        self.touch();
+        self.counts = Counts::of(&text);
+    }
+
+    /// Replaces the whole text as one undo step and puts the cursor at
+    /// `position`.
+    pub fn set_text_at(&mut self, text: String, position: (usize, usize)) {
+        if self.read_only {
+            return;
+        }
+
+        let (line, column) = position;
+
+        self.set_text(text);
+        self.restore_cursor(line, column);
+    }
+
+    /// Puts the cursor back after `Content` was rebuilt, clamped to the
+    /// new text.
+    pub fn restore_cursor(&mut self, line: usize, column: usize) {
+        // A fresh `Content` already starts there.
+        if (line, column) == (0, 0) {
+            return;
+        }
+
+        let text = self.content.text();
+        let offset = cursor::offset(&text, (line, column));
+
+        cursor::move_to(&mut self.content, &text, offset);
+    }
+
+    /// Duplicates the selection right after itself, or the cursor line below
+    /// itself when nothing is selected.
+    pub fn duplicate(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        let text = self.content.text();
+
+        if let Some(range) = cursor::selection_range(&self.content, &text) {
+            let copy = range.end..range.end + range.len();
+            let duplicated = format!("{}{}{}", &text[..range.end], &text[range.clone()], &text[range.end..]);
+
+            self.set_text(duplicated);
+
+            let text = self.content.text();
+            cursor::select(&mut self.content, &text, copy);
+            return;
+        }
+
+        let (line, column) = self.content.cursor_position();
+
+        let (text, _) = lines::lines_mut(&text, |lines| {
+            if let Some(current) = lines.get(line).cloned() {
+                lines.insert(line + 1, current);
+            }
+        });
+
+        self.set_text_at(text, (line + 1, column));
+    }
+
+    /// Swaps the lines under the cursor or selection with the line above or
+    /// below them, keeping the selection on the moved text. Does nothing at
+    /// the edges.
+    pub fn move_lines(&mut self, direction: Direction) {
+        if self.read_only {
+            return;
+        }
+
+        let text = self.content.text();
+        let (first, last, _) = self.affected_lines(&text);
+        let selection = cursor::selection_range(&self.content, &text);
+        let (line, column) = self.content.cursor_position();
+
+        // The moved block shifts by the neighbour line and its newline.
+        let (text, shift) = lines::lines_mut(&text, |lines| {
+            let last = last.min(lines.len() - 1);
+
+            match direction {
+                Direction::Up if first > 0 => {
+                    let neighbour = lines.remove(first - 1);
+                    let shift = neighbour.len() + 1;
+
+                    lines.insert(last, neighbour);
+                    Some(shift)
+                },
+                Direction::Down if last + 1 < lines.len() => {
+                    let neighbour = lines.remove(last + 1);
+                    let shift = neighbour.len() + 1;
+
+                    lines.insert(first, neighbour);
+                    Some(shift)
+                },
+                _ => None
+            }
+        });
+
+        let Some(shift) = shift else { return };
+
+        match (selection, direction) {
+            (Some(range), Direction::Up) => {
+                self.set_text(text);
+
+                let text = self.content.text();
+                cursor::select(&mut self.content, &text, range.start - shift..range.end - shift);
+            },
+            (Some(range), Direction::Down) => {
+                self.set_text(text);
+
+                let text = self.content.text();
+                cursor::select(&mut self.content, &text, range.start + shift..range.end + shift);
+            },
+            (None, Direction::Up) => self.set_text_at(text, (line - 1, column)),
+            (None, Direction::Down) => self.set_text_at(text, (line + 1, column))
+        }
+    }
+
+    /// Removes the lines under the cursor or selection together with their
+    /// newline and keeps the cursor column on the line that moves up.
+    pub fn delete_lines(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        let text = self.content.text();
+        let (first, last, _) = self.affected_lines(&text);
+        let (_, column) = self.content.cursor_position();
+
+        let (text, line) = lines::lines_mut(&text, |lines| {
+            let last = last.min(lines.len() - 1);
+            lines.drain(first..=last);
+
+            if lines.is_empty() {
+                lines.push(String::new());
+            }
+
+            first.min(lines.len() - 1)
+        });
+
+        self.set_text_at(text, (line, column));
+    }
+
+    /// Pulls the following line, or every selected line, up onto the first
+    /// one the way vim's `J` does: leading whitespace becomes one space. The
+    /// cursor lands on the last join so pressing again keeps going.
+    pub fn join_lines(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        let text = self.content.text();
+        let (first, last, _) = self.affected_lines(&text);
+
+        let (text, column) = lines::lines_mut(&text, |lines| {
+            // The empty line after a final newline isn't there to join.
+            let trailing = lines.len() > 1 && lines.last().map_or(false, String::is_empty);
+            let last = last.max(first + 1).min(lines.len() - 1 - usize::from(trailing));
+
+            if last <= first {
+                return None;
+            }
+
+            let mut joined = std::mem::take(&mut lines[first]);
+            let mut column = joined.len();
+
+            for line in lines.drain(first + 1..=last) {
+                let rest = line.trim_start();
+                column = joined.len();
+
+                if !rest.is_empty() && !joined.is_empty() && !joined.ends_with([' ', '\t']) {
+                    joined.push(' ');
+                }
+
+                joined.push_str(rest);
+            }
+
+            lines[first] = joined;
+            Some(column)
+        });
+
+        if let Some(column) = column {
+            self.set_text_at(text, (first, column));
+        }
+    }
+
+    /// Rewrites the text around the cursor with `f`, which gets the text and
+    /// the cursor offset and gives back the new text and offset, or nothing
+    /// when there is nothing to do there.
+    pub fn rewrite_at_cursor(&mut self, f: impl FnOnce(&str, usize) -> Option<(String, usize)>) {
+        if self.read_only || self.content.selection().is_some() {
+            return;
+        }
+
+        let text = self.content.text();
+
+        if let Some((text, offset)) = f(&text, cursor::cursor_offset(&self.content, &text)) {
+            let position = cursor::position(&text, offset);
+            self.set_text_at(text, position);
+        }
+    }
+
+    /// Rewrites the indentation of every line with tabs or spaces and
+    /// returns how many lines changed.
+    pub fn reindent(&mut self, width: usize, tabs: bool) -> usize {
+        if self.read_only {
+            return 0;
+        }
+
+        let position = self.content.cursor_position();
+
+        let (text, changed) = lines::lines_mut(&self.content.text(), |lines| {
+            let mut changed = 0;
+
+            for line in lines.iter_mut() {
+                let reindented = lines::reindent(line, width, tabs);
+
+                if reindented != *line {
+                    *line = reindented;
+                    changed += 1;
+                }
+            }
+
+            changed
+        });
+
+        if changed > 0 {
+            self.set_text_at(text, position);
+        }
+
+        changed
+    }
+
+    /// The lines the cursor or selection covers, and whether it was a
+    /// selection. A selection ending at the very start of a line does not
+    /// take that line in.
+    fn affected_lines(&self, text: &str) -> (usize, usize, bool) {
+        let line_of = |offset: usize| text[..offset].matches('\n').count();
+
+        match cursor::selection_range(&self.content, text) {
+            Some(range) => {
+                let first = line_of(range.start);
+                let mut last = line_of(range.end);
+
+                if last > first && text[..range.end].ends_with('\n') {
+                    last -= 1;
+                }
+
+                (first, last, true)
+            },
+            None => {
+                let (line, _) = self.content.cursor_position();
+                (line, line, false)
+            }
+        }
+    }
+
+    /// Rewrites lines `first..=last` with `f`, keeping the cursor on its line
+    /// or, after a selection, reselecting the whole lines so the command can
+    /// be repeated.
+    fn edit_lines<T>(&mut self, f: impl FnOnce(&mut Vec<String>) -> T) -> Option<T> {
+        if self.read_only {
+            return None;
+        }
+
+        let original = self.content.text();
+        let (first, last, selected) = self.affected_lines(&original);
+        let (_, column) = self.content.cursor_position();
+
+        // The block may come back shorter, so it is edited on its own.
+        let (text, (result, last)) = lines::lines_mut(&original, |lines| {
+            let last = last.min(lines.len() - 1);
+            let mut block = lines.drain(first..=last).collect::<Vec<_>>();
+
+            let result = f(&mut block);
+            let last = first + block.len().max(1) - 1;
+
+            lines.splice(first..first, block);
+            (result, last)
+        });
+
+        // Nothing changed, so there is nothing to undo either.
+        if text == original {
+            return Some(result);
+        }
+
+        if selected {
+            let range = cursor::offset(&text, (first, 0))..cursor::offset(&text, (last, usize::MAX));
+            self.set_text(text);
+
+            let text = self.content.text();
+            cursor::select(&mut self.content, &text, range);
+        } else {
+            self.set_text_at(text, (first, column));
+        }
+
+        Some(result)
+    }
+
+    /// Applies the clean-ups turned on in `options` as one undo step, so the
+    /// buffer shows what ends up on disk.
+    pub fn prepare_save(&mut self, options: &SaveOptions) {
+        if self.read_only {
+            return;
+        }
+
+        let position = self.content.cursor_position();
+        let original = self.content.text();
+        let text = lines::prepare_save(&original, options, position.0);
+
+        if text != original {
+            self.set_text_at(text, position);
+        }
+    }
+
+    /// Whether the selection reaches over more than one line.
+    pub fn spans_lines(&self) -> bool {
+        self.content.selection().map_or(false, |selection| selection.contains('\n'))
+    }
+
+    /// Puts `unit` in front of every non-empty line under the cursor or
+    /// selection.
+    pub fn indent(&mut self, unit: &str) {
+        self.edit_lines(|lines| {
+            for line in lines.iter_mut().filter(|line| !line.is_empty()) {
+                line.insert_str(0, unit);
+            }
+        });
+    }
+
+    /// Takes one level of `width` columns off every line under the cursor or
+    /// selection, leaving lines already at column zero alone.
+    pub fn dedent(&mut self, width: usize) {
+        self.edit_lines(|lines| {
+            for line in lines.iter_mut() {
+                *line = lines::dedent(line, width).to_owned();
+            }
+        });
+    }
+
+    /// Like `edit_lines`, but without a selection it takes every line, less
+    /// the empty one after a final newline.
+    fn edit_selected_or_all<T>(&mut self, f: impl FnOnce(&mut Vec<String>) -> T) -> Option<T> {
+        if self.content.selection().is_some() {
+            return self.edit_lines(f);
+        }
+
+        if self.read_only {
+            return None;
+        }
+
+        let original = self.content.text();
+        let position = self.content.cursor_position();
+
+        let (text, result) = lines::lines_mut(&original, |lines| {
+            let trailing = (lines.len() > 1 && lines.last().map_or(false, String::is_empty))
+                .then(|| lines.pop())
+                .flatten();
+
+            let result = f(lines);
+            lines.extend(trailing);
+
+            result
+        });
+
+        if text != original {
+            self.set_text_at(text, position);
+        }
+
+        Some(result)
+    }
+
+    pub fn sort_lines(&mut self, sort: Sort) {
+        self.edit_selected_or_all(|lines| lines::sort(lines, sort));
+    }
+
+    /// Changes the case of the selection, or of the word under the cursor,
+    /// and leaves the result selected.
+    pub fn change_case(&mut self, case: Case) {
+        if self.read_only {
+            return;
+        }
+
+        let text = self.content.text();
+        let offset = cursor::cursor_offset(&self.content, &text);
+
+        let Some(range) = cursor::selection_range(&self.content, &text).or_else(|| cursor::word_at(&text, offset)) else {
+            return;
+        };
+
+        let changed = lines::change_case(&text[range.clone()], case);
+
+        if changed == text[range.clone()] {
+            return;
+        }
+
+        let selected = range.start..range.start + changed.len();
+        let text = format!("{}{}{}", &text[..range.start], changed, &text[range.end..]);
+
+        self.set_text(text);
+
+        let text = self.content.text();
+        cursor::select(&mut self.content, &text, selected);
+    }
+
+    /// Puts the numbers from `sequence` at every cursor, in document order,
+    /// or else at the start of every selected line.
+    pub fn insert_sequence(&mut self, mut sequence: impl Iterator<Item = String>) {
+        if self.read_only {
+            return;
+        }
+
+        if self.cursors.is_empty() {
+            self.edit_lines(|lines| {
+                for (line, number) in lines.iter_mut().zip(sequence) {
+                    line.insert_str(0, &number);
+                }
+            });
+
+            return;
+        }
+
+        let text = self.content.text();
+        let main = cursor::selection_range(&self.content, &text).map_or_else(|| cursor::cursor_offset(&self.content, &text), |range| range.start);
+
+        let mut offsets = self.cursors.iter().map(|range| range.start).chain([main]).collect::<Vec<_>>();
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let mut result = String::with_capacity(text.len());
+        let mut end = 0;
+
+        for offset in offsets {
+            result.push_str(&text[end..offset]);
+            result.extend(sequence.next());
+            end = offset;
+        }
+
+        result.push_str(&text[end..]);
+
+        let position = self.content.cursor_position();
+        self.set_text_at(result, position);
+    }
+
+    /// Lines up the first `separator` on every selected line and returns how
+    /// many lines moved.
+    pub fn align(&mut self, separator: &str, width: usize) -> usize {
+        self.edit_lines(|lines| lines::align(lines, separator, width)).unwrap_or(0)
+    }
+
+    /// Replaces the selected expression with its value, or with `append`
+    /// keeps it and adds ` = value`. The text is left alone on an error.
+    pub fn evaluate(&mut self, append: bool) -> Result<(), &'static str> {
+        if self.read_only {
+            return Err("Документ только для чтения");
+        }
+
+        let text = self.content.text();
+        let Some(range) = cursor::selection_range(&self.content, &text) else { return Err("Нет выделения") };
+
+        let expression = &text[range.clone()];
+        let value = calc::format(calc::evaluate(expression)?);
+
+        let result = if append { format!("{} = {value}", expression.trim_end()) } else { value };
+        let selected = range.start..range.start + result.len();
+        let text = format!("{}{}{}", &text[..range.start], result, &text[range.end..]);
+
+        self.set_text(text);
+
+        let text = self.content.text();
+        cursor::select(&mut self.content, &text, selected);
+        Ok(())
+    }
+
+    /// Re-wraps the selected lines, or the paragraph around the cursor, to
+    /// `column`.
+    pub fn reflow(&mut self, column: usize, width: usize) {
+        if self.read_only {
+            return;
+        }
+
+        if self.content.selection().is_some() {
+            self.edit_lines(|lines| lines::reflow(lines, column, width));
+            return;
+        }
+
+        let original = self.content.text();
+        let (line, _) = self.content.cursor_position();
+
+        let (text, last) = lines::lines_mut(&original, |lines| {
+            let is_blank = |line: &String| line.trim().is_empty();
+
+            if lines.get(line).map_or(true, is_blank) {
+                return None;
+            }
+
+            let first = lines[..line].iter().rposition(is_blank).map_or(0, |blank| blank + 1);
+            let end = lines[line..].iter().position(is_blank).map_or(lines.len(), |blank| line + blank);
+
+            let mut paragraph = lines.drain(first..end).collect::<Vec<_>>();
+            lines::reflow(&mut paragraph, column, width);
+
+            let last = first + paragraph.len().max(1) - 1;
+            let length = paragraph.last().map_or(0, String::len);
+
+            lines.splice(first..first, paragraph);
+            Some((last, length))
+        });
+
+        if let Some(position) = last.filter(|_| text != original) {
+            self.set_text_at(text, position);
+        }
+    }
+
+    pub fn reverse_lines(&mut self) {
+        self.edit_selected_or_all(|lines| lines.reverse());
+    }
+
+    /// Drops repeated lines, keeping the first of each, and returns how many
+    /// went. With `adjacent` only runs of equal lines collapse, like `uniq`.
+    pub fn unique_lines(&mut self, adjacent: bool, ignore_trailing_whitespace: bool) -> usize {
+        self.edit_selected_or_all(|lines| lines::unique(lines, adjacent, ignore_trailing_whitespace))
+            .unwrap_or(0)
+    }
+
+    /// Returns `false` when the language has no line comments.
+    pub fn toggle_line_comment(&mut self) -> bool {
+        let Some(token) = comment::line_token(self.syntax()) else { return false };
+
+        self.edit_lines(|lines| comment::toggle_lines(lines, token));
+        true
+    }
+
+    /// Wraps the selection in block comment delimiters, or unwraps it when
+    /// it is exactly one block comment. Refuses to touch a selection that
+    /// would end up in a nested or broken comment.
+    pub fn toggle_block_comment(&mut self) -> Result<(), &'static str> {
+        let Some((open, close)) = comment::block_tokens(self.syntax()) else {
+            return Err("Для этого языка нет блочных комментариев");
+        };
+
+        let text = self.content.text();
+        let Some(range) = cursor::selection_range(&self.content, &text) else { return Err("Нет выделения") };
+        let selected = &text[range.clone()];
+
+        let inner = selected.strip_prefix(open).and_then(|rest| rest.strip_suffix(close));
+
+        if comment::is_inside_block(&text, range.start, (open, close))
+            || comment::is_inside_block(&text, range.end, (open, close))
+        {
+            return Err("Выделение пересекается с блочным комментарием");
+        }
+
+        if let Some(inner) = inner {
+            if inner.contains(open) || inner.contains(close) {
+                return Err("Выделение пересекается с блочным комментарием");
+            }
+
+            let unwrapped = range.start..range.start + inner.len();
+            let text = format!("{}{}{}", &text[..range.start], inner, &text[range.end..]);
+
+            self.set_text(text);
+
+            let text = self.content.text();
+            cursor::select(&mut self.content, &text, unwrapped);
+            return Ok(());
+        }
+
+        if selected.contains(open) || selected.contains(close) {
+            return Err("Выделение пересекается с блочным комментарием");
+        }
+
+        let wrapped = range.start..range.end + open.len() + close.len();
+        let text = format!("{}{}{}{}{}", &text[..range.start], open, selected, close, &text[range.end..]);
+
+        self.set_text(text);
+
+        let text = self.content.text();
+        cursor::select(&mut self.content, &text, wrapped);
+        Ok(())
+    }
+
+    pub fn undo(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        if let Some(text) = self.history.undo(self.content.text()) {
+            let (line, column) = self.content.cursor_position();
+
+            self.content = text_editor::Content::with(&text);
+            self.cursors.clear();
+            self.restore_cursor(line, column);
+            self.touch();
+            self.counts = Counts::of(&text);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        if let Some(text) = self.history.redo(self.content.text()) {
+            let (line, column) = self.content.cursor_position();
+
+            self.content = text_editor::Content::with(&text);
+            self.cursors.clear();
+            self.restore_cursor(line, column);
+            self.touch();
+            self.counts = Counts::of(&text);
+        }
+    }
+
+    fn touch(&mut self) {
+        self.revision += 1;
+
+        // Undoing back to what is on disk makes the document clean again.
+        if self.fingerprint(self.mixed_endings) == self.saved_fingerprint {
+            self.saved_revision = self.revision;
+        }
+    }
+
+    fn mark_clean(&mut self) {
+        self.saved_revision = self.revision;
+        self.saved_fingerprint = self.fingerprint(self.mixed_endings);
+    }
+
+    fn fingerprint(&self, mixed_endings: bool) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.content.text().hash(&mut hasher);
+        self.format.encoding.name().hash(&mut hasher);
+        self.format.bom.hash(&mut hasher);
+        self.line_ending.hash(&mut hasher);
+        mixed_endings.hash(&mut hasher);
+
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(document: &mut Document, text: &str) {
+        for c in text.chars() {
+            document.edit(text_editor::Action::Edit(text_editor::Edit::Insert(c)));
+        }
+    }
+
+    #[test]
+    fn a_new_document_is_not_dirty() {
+        let document = Document::new(0, None, LineEnding::Lf);
+
+        assert!(!document.is_dirty());
+        assert!(document.is_placeholder());
+    }
+
+    #[test]
+    fn undoing_an_edit_makes_the_document_clean_again() {
+        let mut document = Document::new(0, None, LineEnding::Lf);
+
+        type_text(&mut document, "hello");
+        assert!(document.is_dirty());
+
+        document.undo();
+        assert!(!document.is_dirty());
+
+        document.redo();
+        assert!(document.is_dirty());
+    }
+}
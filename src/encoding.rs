@@ -0,0 +1,174 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251, WINDOWS_1252};
+
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub lossy: bool
+}
+
+pub fn decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, length)) = Encoding::for_bom(bytes) {
+        let (text, lossy) = encoding.decode_without_bom_handling(&bytes[length..]);
+
+        return Decoded { text: text.into_owned(), encoding, bom: true, lossy };
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Decoded { text: text.to_owned(), encoding: UTF_8, bom: false, lossy: false };
+    }
+
+    if looks_like_utf8(bytes) {
+        let text = String::from_utf8_lossy(bytes).into_owned();
+
+        return Decoded { text, encoding: UTF_8, bom: false, lossy: true };
+    }
+
+    let encoding = guess_legacy(bytes);
+    let (text, lossy) = encoding.decode_without_bom_handling(bytes);
+
+    Decoded { text: text.into_owned(), encoding, bom: false, lossy }
+}
+
+/// Looks at the head of the file the way `file(1)` does: a NUL byte or a
+/// lot of control characters means it is not text. Files with a BOM are
+/// exempt since UTF-16 text is full of NULs.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    const SNIFF_LEN: usize = 8 * 1024;
+
+    if Encoding::for_bom(bytes).is_some() {
+        return false;
+    }
+
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+
+    if head.contains(&0) {
+        return true;
+    }
+
+    let control = head
+        .iter()
+        .filter(|byte| byte.is_ascii_control() && !matches!(byte, b'\n' | b'\r' | b'\t' | 0x0C | 0x1B))
+        .count();
+
+    control * 10 > head.len()
+}
+
+/// Shows arbitrary bytes as text for viewing only; anything unprintable
+/// becomes a replacement character.
+pub fn decode_binary(bytes: &[u8]) -> Decoded {
+    let text = String::from_utf8_lossy(bytes)
+        .chars()
+        .map(|c| if c.is_control() && !matches!(c, '\n' | '\t') { '\u{FFFD}' } else { c })
+        .collect();
+
+    Decoded { text, encoding: UTF_8, bom: false, lossy: true }
+}
+
+pub fn decode_with(bytes: &[u8], encoding: &'static Encoding) -> Decoded {
+    let (bom, body) = match Encoding::for_bom(bytes) {
+        Some((found, length)) if found == encoding => (true, &bytes[length..]),
+        _ => (false, bytes)
+    };
+
+    let (text, lossy) = encoding.decode_without_bom_handling(body);
+
+    Decoded { text: text.into_owned(), encoding, bom, lossy }
+}
+
+/// Fails with the first character the code page has no byte for. encoding_rs
+/// would write it as an HTML entity, which corrupts the file.
+pub fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> Result<Vec<u8>, char> {
+    let mut bytes = Vec::with_capacity(text.len());
+
+    // encoding_rs can decode UTF-16 but only ever encodes to UTF-8, so the
+    // two UTF-16 variants are serialised by hand.
+    if encoding == UTF_16LE {
+        if bom {
+            bytes.extend_from_slice(&[0xFF, 0xFE]);
+        }
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    } else if encoding == UTF_16BE {
+        if bom {
+            bytes.extend_from_slice(&[0xFE, 0xFF]);
+        }
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    } else {
+        if bom && encoding == UTF_8 {
+            bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+        }
+        let (encoded, _, had_errors) = encoding.encode(text);
+
+        if had_errors {
+            let unmappable = text.chars().find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2);
+            return Err(unmappable.unwrap_or('\u{FFFD}'));
+        }
+
+        bytes.extend_from_slice(&encoded);
+    }
+
+    Ok(bytes)
+}
+
+/// A UTF-8 file with a few broken bytes is better shown with replacement
+/// characters than reinterpreted as a legacy code page, which would garble
+/// every non-ASCII letter in it.
+fn looks_like_utf8(bytes: &[u8]) -> bool {
+    let mut rest = bytes;
+    let mut valid = 0;
+    let mut invalid = 0;
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(text) => {
+                valid += text.chars().filter(|c| !c.is_ascii()).count();
+                break;
+            },
+            Err(error) => {
+                let (good, bad) = rest.split_at(error.valid_up_to());
+                valid += std::str::from_utf8(good).unwrap_or_default().chars().filter(|c| !c.is_ascii()).count();
+                invalid += 1;
+
+                match error.error_len() {
+                    Some(length) => rest = &bad[length..],
+                    None => break
+                }
+            }
+        }
+    }
+
+    valid > invalid
+}
+
+/// Tells Cyrillic (Windows-1251) from Western (Windows-1252) text: Russian
+/// prose is mostly letters from the upper half of the code page, while
+/// Western text is mostly ASCII with the odd accented letter.
+fn guess_legacy(bytes: &[u8]) -> &'static Encoding {
+    let letters = bytes.iter().filter(|byte| byte.is_ascii_alphabetic()).count();
+    let high = bytes.iter().filter(|byte| **byte >= 0xC0).count();
+
+    if high > letters {
+        WINDOWS_1251
+    } else {
+        WINDOWS_1252
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_pages_refuse_characters_they_cannot_hold() {
+        assert_eq!(encode("Привет", WINDOWS_1251, false), Ok(vec![0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2]));
+        assert_eq!(encode("Привет", WINDOWS_1252, false), Err('П'));
+        assert_eq!(encode("a€b✓", WINDOWS_1251, false), Err('✓'));
+    }
+
+    #[test]
+    fn unicode_encodings_hold_everything() {
+        assert_eq!(encode("✓", UTF_8, true), Ok(vec![0xEF, 0xBB, 0xBF, 0xE2, 0x9C, 0x93]));
+        assert_eq!(encode("✓", UTF_16LE, false), Ok(vec![0x13, 0x27]));
+    }
+}
@@ -0,0 +1,122 @@
+use iced::advanced::text::Highlighter as _;
+use iced::highlighter::{self, Highlighter};
+use iced::Color;
+use printpdf::{Mm, PdfDocument};
+
+use crate::lines;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const MM_PER_POINT: f32 = 25.4 / 72.0;
+
+/// Renders `text` as a standalone HTML page, colored the way the editor
+/// shows it.
+pub fn to_html(text: &str, settings: &highlighter::Settings, title: &str, background: Color) -> String {
+    let mut highlighter = Highlighter::new(settings);
+    let mut body = String::with_capacity(text.len() * 2);
+
+    for line in text.split('\n') {
+        let mut end = 0;
+
+        for (range, highlight) in highlighter.highlight_line(line) {
+            if range.start < end || range.end > line.len() {
+                continue;
+            }
+
+            escape(&line[end..range.start], &mut body);
+
+            match highlight.to_format().color {
+                Some(color) => {
+                    body.push_str(&format!("<span style=\"color: {}\">", css(color)));
+                    escape(&line[range.clone()], &mut body);
+                    body.push_str("</span>");
+                },
+                None => escape(&line[range.clone()], &mut body)
+            }
+
+            end = range.end;
+        }
+
+        escape(&line[end..], &mut body);
+        body.push('\n');
+    }
+
+    body.pop();
+
+    let mut title_html = String::new();
+    escape(title, &mut title_html);
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title_html}</title>\n</head>\n\
+         <body style=\"margin: 0; background: {background}\">\n\
+         <pre style=\"margin: 0; padding: 1em; font-family: monospace\">{body}</pre>\n</body>\n</html>\n",
+        background = css(background)
+    )
+}
+
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c)
+        }
+    }
+}
+
+fn css(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Lays `text` out on A4 pages at the editor's font size, wrapping long
+/// lines at the page edge. The font is embedded, since the ones built into
+/// PDF readers only cover Latin-1 and Cyrillic would print as blanks.
+pub fn to_pdf(text: &str, title: &str, font_size: f32, tab_width: usize, line_numbers: bool) -> Result<Vec<u8>, printpdf::Error> {
+    // Pixels on screen to points on paper.
+    let size = font_size * 0.75;
+    let line_height = size * 1.3 * MM_PER_POINT;
+    let char_width = size * 0.6 * MM_PER_POINT;
+
+    let columns = (((PAGE_WIDTH - 2.0 * MARGIN) / char_width) as usize).max(1);
+    let rows = (((PAGE_HEIGHT - 2.0 * MARGIN) / line_height) as usize).max(1);
+
+    let digits = text.split('\n').count().to_string().len();
+    let gutter = if line_numbers { digits + 1 } else { 0 };
+    let width = columns.saturating_sub(gutter).max(1);
+
+    let mut printed = Vec::new();
+
+    for (index, line) in text.split('\n').enumerate() {
+        let chars = lines::expand_tabs(line, tab_width).chars().collect::<Vec<_>>();
+        let mut chunks = chars.chunks(width).map(|chunk| chunk.iter().collect::<String>());
+
+        let first = chunks.next().unwrap_or_default();
+
+        printed.push(if line_numbers { format!("{:>digits$} {first}", index + 1) } else { first });
+        printed.extend(chunks.map(|chunk| format!("{}{chunk}", " ".repeat(gutter))));
+    }
+
+    let (document, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "text");
+    let font = document.add_external_font(include_bytes!("../fonts/DejaVuSansMono.ttf").as_slice())?;
+
+    let mut layer = document.get_page(page).get_layer(layer);
+
+    for (index, line) in printed.iter().enumerate() {
+        let row = index % rows;
+
+        if index > 0 && row == 0 {
+            let (page, next) = document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "text");
+            layer = document.get_page(page).get_layer(next);
+        }
+
+        let y = PAGE_HEIGHT - MARGIN - (row + 1) as f32 * line_height;
+        layer.use_text(line.as_str(), size, Mm(MARGIN), Mm(y), &font);
+    }
+
+    document.save_to_bytes()
+}
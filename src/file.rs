@@ -0,0 +1,600 @@
+use std::fmt;
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use crate::encoding;
+use crate::line_ending::LineEnding;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    DialogClosed,
+    IOFailed(io::ErrorKind),
+    TempWriteFailed(io::ErrorKind),
+    RenameFailed(io::ErrorKind),
+    BackupFailed(io::ErrorKind),
+    MetadataNotRestored(io::ErrorKind),
+    BinaryFile(PathBuf),
+    ExportFailed(String),
+    Unencodable { encoding: &'static str, character: char },
+    TooLarge { path: PathBuf, size: u64 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64
+}
+
+impl Stamp {
+    /// The size on disk when the stamp was taken.
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+}
+
+impl From<&Metadata> for Stamp {
+    fn from(metadata: &Metadata) -> Self {
+        Stamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub encoding: &'static Encoding,
+    pub bom: bool
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            encoding: UTF_8,
+            bom: false
+        }
+    }
+}
+
+impl Format {
+    pub fn all() -> Vec<Format> {
+        [
+            (UTF_8, false),
+            (UTF_8, true),
+            (UTF_16LE, true),
+            (UTF_16BE, true),
+            (WINDOWS_1251, false),
+            (WINDOWS_1252, false)
+        ]
+        .into_iter()
+        .map(|(encoding, bom)| Format { encoding, bom })
+        .collect()
+    }
+
+    pub fn supports_bom(&self) -> bool {
+        self.encoding == UTF_8 || self.encoding == UTF_16LE || self.encoding == UTF_16BE
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bom {
+            write!(f, "{} BOM", self.encoding.name())
+        } else {
+            write!(f, "{}", self.encoding.name())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Loaded {
+    pub path: PathBuf,
+    pub text: Arc<String>,
+    pub stamp: Option<Stamp>,
+    pub read_only: bool,
+    pub format: Format,
+    pub lossy: bool,
+    pub line_ending: LineEnding,
+    pub mixed_endings: bool,
+    pub binary: bool,
+    pub partial: bool
+}
+
+#[derive(Debug, Clone)]
+pub struct Saved {
+    pub path: PathBuf,
+    pub stamp: Option<Stamp>,
+    pub warning: Option<Error>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    pub backup: bool,
+    pub backup_suffix: String,
+    pub trim_on_save: bool,
+    // Leaves the line being typed on alone, so a space just typed survives
+    // an auto-save.
+    pub trim_skips_cursor_line: bool,
+    pub final_newline: FinalNewline
+}
+
+/// What saving does to the newlines at the end of the file. The buffer only
+/// holds `\n`, so whatever is added is written with the file's line ending.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinalNewline {
+    #[default]
+    Keep,
+    Ensure,
+    Single
+}
+
+impl FinalNewline {
+    pub const ALL: &'static [FinalNewline] = &[FinalNewline::Keep, FinalNewline::Ensure, FinalNewline::Single];
+}
+
+impl fmt::Display for FinalNewline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FinalNewline::Keep => "Конец файла как есть",
+            FinalNewline::Ensure => "Перевод строки в конце",
+            FinalNewline::Single => "Ровно один перевод строки"
+        })
+    }
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            backup: false,
+            backup_suffix: String::from(".bak"),
+            trim_on_save: false,
+            trim_skips_cursor_line: true,
+            final_newline: FinalNewline::Keep
+        }
+    }
+}
+
+impl Error {
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Error::DialogClosed => None,
+            Error::IOFailed(error) => Some(error.to_string()),
+            Error::TempWriteFailed(error) => Some(format!("Не удалось сохранить: {error}. Исходный файл не изменён")),
+            Error::RenameFailed(error) => Some(format!("Не удалось заменить файл: {error}. Файл мог быть повреждён")),
+            Error::BackupFailed(error) => Some(format!("Не удалось создать резервную копию: {error}. Файл не сохранён")),
+            Error::MetadataNotRestored(error) => Some(format!("Файл сохранён, но права доступа не восстановлены: {error}")),
+            Error::BinaryFile(path) => Some(format!("{} похож на двоичный файл и не был открыт", path.display())),
+            Error::ExportFailed(error) => Some(format!("Не удалось экспортировать: {error}")),
+            Error::Unencodable { encoding, character } => Some(format!(
+                "Символ «{character}» нельзя записать в {encoding}. Файл не сохранён, выберите UTF-8"
+            )),
+            Error::TooLarge { path, size } => Some(format!("{} слишком большой ({})", path.display(), format_size(*size)))
+        }
+    }
+}
+
+/// Writes `text` to `path`, or asks for a path first, starting the dialog
+/// in `directory`.
+pub async fn save_file(path: Option<PathBuf>, directory: Option<PathBuf>, text: String, format: Format, line_ending: LineEnding, options: SaveOptions) -> Result<Saved, Error> {
+    let path = if let Some(path) = path { path } else {
+        dialog(directory)
+        .set_title("Choose a file name...")
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)
+        .map(|handle| handle.path().to_owned())?
+    };
+
+    // Checked before the backup, so a refused save leaves no trace.
+    let bytes = encoding::encode(&line_ending.apply(&text), format.encoding, format.bom)
+        .map_err(|character| Error::Unencodable { encoding: format.encoding.name(), character })?;
+
+    if options.backup {
+        back_up(&path, &options.backup_suffix).await?;
+    }
+
+    let warning = write_atomically(&path, &bytes).await?;
+
+    let stamp = stamp(path.clone()).await;
+
+    Ok(Saved { path, stamp, warning })
+}
+
+/// Asks where to put an exported copy, suggesting `name`, and writes
+/// `bytes` there.
+pub async fn export_file(bytes: Vec<u8>, directory: Option<PathBuf>, name: String, extension: &'static str) -> Result<PathBuf, Error> {
+    let path = dialog(directory)
+        .set_title("Экспорт")
+        .set_file_name(&name)
+        .add_filter(extension, &[extension])
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)?
+        .path()
+        .to_owned();
+
+    tokio::fs::write(&path, bytes)
+    .await
+    .map_err(|error| Error::IOFailed(error.kind()))?;
+
+    Ok(path)
+}
+
+pub async fn save_file_as(current: Option<PathBuf>, directory: Option<PathBuf>, text: String, format: Format, line_ending: LineEnding, options: SaveOptions) -> Result<Saved, Error> {
+    let mut dialog = dialog(directory).set_title("Choose a file name...");
+
+    if let Some(current) = current.as_deref() {
+        if let Some(directory) = current.parent() {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(name) = current.file_name().and_then(|name| name.to_str()) {
+            dialog = dialog.set_file_name(name);
+        }
+    }
+
+    let path = dialog
+    .save_file()
+    .await
+    .ok_or(Error::DialogClosed)
+    .map(|handle| handle.path().to_owned())?;
+
+    save_file(Some(path), None, text, format, line_ending, options).await
+}
+
+/// Like `load_file`, but refuses files over `limit` bytes so the caller can
+/// ask before pulling them into memory.
+pub async fn open_file(path: PathBuf, limit: u64) -> Result<Loaded, Error> {
+    let path = absolute(path).await;
+
+    let size = tokio::fs::metadata(&path)
+    .await
+    .map_err(|error| Error::IOFailed(error.kind()))?
+    .len();
+
+    if size > limit {
+        return Err(Error::TooLarge { path, size });
+    }
+
+    load_file(path).await
+}
+
+pub async fn load_file(path: PathBuf) -> Result<Loaded, Error> {
+    load(path, Decode::Detect).await
+}
+
+pub async fn reopen_file(path: PathBuf, format: Format) -> Result<Loaded, Error> {
+    load(path, Decode::With(format)).await
+}
+
+/// Opens a file that `load_file` refused as binary. The result is a lossy,
+/// read-only view that can never be saved back over the original.
+pub async fn load_binary(path: PathBuf) -> Result<Loaded, Error> {
+    load(path, Decode::Binary).await
+}
+
+/// Reads only the first `lines` lines of the file, as a read-only preview
+/// that can't be saved over the full original.
+pub async fn preview_file(path: PathBuf, lines: usize) -> Result<Loaded, Error> {
+    let path = absolute(path).await;
+
+    let file = tokio::fs::File::open(&path)
+    .await
+    .map_err(|error| Error::IOFailed(error.kind()))?;
+
+    let mut reader = tokio::io::BufReader::new(file);
+    let mut bytes = Vec::new();
+
+    for _ in 0..lines {
+        let read = reader
+            .read_until(b'\n', &mut bytes)
+            .await
+            .map_err(|error| Error::IOFailed(error.kind()))?;
+
+        if read == 0 {
+            break;
+        }
+    }
+
+    let mut loaded = decode(path, bytes, Decode::Detect).await?;
+    loaded.read_only = true;
+    loaded.partial = true;
+
+    Ok(loaded)
+}
+
+/// Reads everything piped in. Only called for a `-` argument when stdin isn't
+/// a terminal, so it never sits waiting for the keyboard.
+pub fn read_stdin() -> Option<(String, LineEnding)> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes).ok()?;
+
+    let decoded = encoding::decode(&bytes);
+    let (line_ending, _) = LineEnding::detect(&decoded.text);
+
+    Some((LineEnding::normalize(&decoded.text), line_ending))
+}
+
+/// Resolves `path` against the working directory so documents, recent files
+/// and prompts all see one stable location. Falls back to the path as given,
+/// and the read that follows reports why it doesn't exist.
+async fn absolute(path: PathBuf) -> PathBuf {
+    tokio::fs::canonicalize(&path).await.unwrap_or(path)
+}
+
+/// `absolute` for paths known before anything is loaded, such as the ones
+/// on the command line, so their tabs match the documents loaded into them.
+pub fn absolute_now(path: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+#[derive(Debug, Clone)]
+pub enum Progress {
+    Advanced(f32),
+    Finished(Result<Loaded, Error>)
+}
+
+enum Reading {
+    Start(PathBuf),
+    Chunks { path: PathBuf, file: tokio::fs::File, bytes: Vec<u8>, total: u64 },
+    Done
+}
+
+/// Loads a file chunk by chunk, reporting how far it got. Dropping the
+/// subscription stops the read without touching anything.
+pub fn load_with_progress(id: usize, path: PathBuf) -> iced::Subscription<Progress> {
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    iced::subscription::unfold(id, Reading::Start(path), |state| async move {
+        match state {
+            Reading::Start(path) => match tokio::fs::File::open(&path).await {
+                Ok(file) => {
+                    let total = file.metadata().await.map(|metadata| metadata.len()).unwrap_or(0);
+                    let bytes = Vec::with_capacity(total as usize);
+
+                    (Progress::Advanced(0.0), Reading::Chunks { path, file, bytes, total })
+                },
+                Err(error) => (Progress::Finished(Err(Error::IOFailed(error.kind()))), Reading::Done)
+            },
+            Reading::Chunks { path, mut file, mut bytes, total } => {
+                let mut chunk = vec![0; CHUNK_SIZE];
+
+                match file.read(&mut chunk).await {
+                    Ok(0) => (Progress::Finished(decode(path, bytes, Decode::Detect).await), Reading::Done),
+                    Ok(read) => {
+                        bytes.extend_from_slice(&chunk[..read]);
+                        let progress = if total == 0 { 1.0 } else { bytes.len() as f32 / total as f32 };
+
+                        (Progress::Advanced(progress.min(1.0)), Reading::Chunks { path, file, bytes, total })
+                    },
+                    Err(error) => (Progress::Finished(Err(Error::IOFailed(error.kind()))), Reading::Done)
+                }
+            },
+            Reading::Done => iced::futures::future::pending().await
+        }
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Decode {
+    Detect,
+    With(Format),
+    Binary
+}
+
+async fn load(path: PathBuf, mode: Decode) -> Result<Loaded, Error> {
+    let path = absolute(path).await;
+
+    let bytes = tokio::fs::read(&path)
+    .await
+    .map_err(|error| Error::IOFailed(error.kind()))?;
+
+    decode(path, bytes, mode).await
+}
+
+async fn decode(path: PathBuf, bytes: Vec<u8>, mode: Decode) -> Result<Loaded, Error> {
+    let binary = matches!(mode, Decode::Binary);
+
+    if !binary && encoding::looks_binary(&bytes) {
+        return Err(Error::BinaryFile(path));
+    }
+
+    let decoded = match mode {
+        Decode::Detect => encoding::decode(&bytes),
+        Decode::With(format) => encoding::decode_with(&bytes, format.encoding),
+        Decode::Binary => encoding::decode_binary(&bytes)
+    };
+
+    let (line_ending, mixed_endings) = LineEnding::detect(&decoded.text);
+    let text = LineEnding::normalize(&decoded.text);
+
+    let stamp = stamp(path.clone()).await;
+    let read_only = binary || is_read_only(&path).await;
+
+    Ok(Loaded {
+        path,
+        text: Arc::new(text),
+        stamp,
+        read_only,
+        format: match mode {
+            Decode::With(format) => format,
+            _ => Format { encoding: decoded.encoding, bom: decoded.bom }
+        },
+        lossy: decoded.lossy,
+        line_ending,
+        mixed_endings,
+        binary,
+        partial: false
+    })
+}
+
+async fn is_read_only(path: &Path) -> bool {
+    let readonly = tokio::fs::metadata(path)
+        .await
+        .map(|metadata| metadata.permissions().readonly())
+        .unwrap_or(false);
+
+    readonly || tokio::fs::OpenOptions::new().write(true).open(path).await.is_err()
+}
+
+pub async fn stamp(path: PathBuf) -> Option<Stamp> {
+    tokio::fs::metadata(path)
+    .await
+    .ok()
+    .map(|metadata| Stamp::from(&metadata))
+}
+
+pub async fn pick_file(directory: Option<PathBuf>, limit: u64) -> Result<Loaded, Error> {
+    let handle = dialog(directory)
+        .set_title("Выберите файл")
+        .pick_file()
+        .await.ok_or(Error::DialogClosed)?;
+
+    let path = handle.path(); 
+
+    open_file(path.to_owned(), limit).await
+}
+
+/// A file dialog that opens in `directory`, when there is one to go back to.
+fn dialog(directory: Option<PathBuf>) -> rfd::AsyncFileDialog {
+    let dialog = rfd::AsyncFileDialog::new();
+
+    match directory.filter(|directory| directory.is_dir()) {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog
+    }
+}
+
+async fn back_up(path: &Path, suffix: &str) -> Result<(), Error> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => {},
+        _ => return Ok(())
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+
+    tokio::fs::copy(path, path.with_file_name(name))
+    .await
+    .map(|_| ())
+    .map_err(|error| Error::BackupFailed(error.kind()))
+}
+
+/// Returns a warning when the new file could not be given the original's
+/// permissions or owner; the contents are saved either way.
+pub async fn write_atomically(path: &Path, bytes: &[u8]) -> Result<Option<Error>, Error> {
+    let temp = temp_path(path);
+    let original = tokio::fs::metadata(path).await.ok();
+
+    if let Err(error) = write_synced(&temp, bytes).await {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(Error::TempWriteFailed(error.kind()));
+    }
+
+    let warning = match original.as_ref() {
+        Some(original) => restore_metadata(&temp, original)
+            .await
+            .err()
+            .map(|error| Error::MetadataNotRestored(error.kind())),
+        None => None
+    };
+
+    if tokio::fs::rename(&temp, path).await.is_ok() {
+        return Ok(warning);
+    }
+
+    // Some filesystems (network shares, or Windows while another process
+    // holds the destination open) refuse to replace the file, so fall back
+    // to overwriting it in place.
+    let _ = tokio::fs::remove_file(&temp).await;
+
+    tokio::fs::write(path, bytes)
+    .await
+    .map(|_| None)
+    .map_err(|error| Error::RenameFailed(error.kind()))
+}
+
+async fn restore_metadata(path: &Path, original: &Metadata) -> io::Result<()> {
+    tokio::fs::set_permissions(path, original.permissions()).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let written = tokio::fs::metadata(path).await?;
+
+        if (written.uid(), written.gid()) != (original.uid(), original.gid()) {
+            std::os::unix::fs::chown(path, Some(original.uid()), Some(original.gid()))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = tokio::fs::File::create(path).await?;
+
+    file.write_all(bytes).await?;
+    file.sync_all().await
+}
+
+pub fn format_size(size: u64) -> String {
+    const KILOBYTE: f64 = 1024.0;
+    const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+    match size as f64 {
+        bytes if bytes < KILOBYTE => format!("{size} Б"),
+        bytes if bytes < MEGABYTE => format!("{:.1} КБ", bytes / KILOBYTE),
+        bytes => format!("{:.1} МБ", bytes / MEGABYTE)
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let suffix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default()
+        ^ std::process::id();
+
+    path.with_file_name(format!(".{name}.tmp-{suffix:08x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn saving_keeps_the_permissions_of_the_file_it_replaces() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!("texteditor-permissions-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let path = directory.join("script.sh");
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let warning = write_atomically(&path, b"#!/bin/sh\necho saved\n").await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(warning.is_none());
+        assert_eq!(mode, 0o755);
+        assert_eq!(contents, "#!/bin/sh\necho saved\n");
+    }
+}
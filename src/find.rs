@@ -0,0 +1,214 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+// Keeps a pathological pattern from compiling into a huge automaton; the
+// regex crate itself guarantees the scan is linear in the text.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub regex: bool,
+    pub match_case: bool,
+    pub whole_word: bool
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            regex: false,
+            match_case: true,
+            whole_word: false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Find {
+    pub query: String,
+    pub replacement: String,
+    pub options: Options,
+    pub in_selection: bool,
+    pub error: Option<String>,
+    pattern: Option<Regex>
+}
+
+impl Find {
+    pub fn new(options: Options) -> Self {
+        Find {
+            options,
+            ..Find::default()
+        }
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.compile();
+    }
+
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+        self.compile();
+    }
+
+    /// Every mode goes through one regex: literal queries are escaped, and
+    /// `\b` is Unicode-aware, so whole words work for Cyrillic too.
+    fn compile(&mut self) {
+        self.pattern = None;
+        self.error = None;
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        let mut pattern = if self.options.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+
+        if self.options.whole_word {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(!self.options.match_case)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_SIZE_LIMIT)
+            .build()
+        {
+            Ok(pattern) => self.pattern = Some(pattern),
+            Err(error) => self.error = Some(error.to_string())
+        }
+    }
+
+    pub fn next(&self, text: &str, from: usize) -> Option<Range<usize>> {
+        let matches = self.matches(text);
+
+        matches
+            .iter()
+            .find(|range| range.start >= from)
+            .or(matches.first())
+            .cloned()
+    }
+
+    pub fn previous(&self, text: &str, before: usize) -> Option<Range<usize>> {
+        let matches = self.matches(text);
+
+        matches
+            .iter()
+            .rev()
+            .find(|range| range.end <= before)
+            .or(matches.last())
+            .cloned()
+    }
+
+    /// Every match in `text`. Empty regex matches are skipped since there is
+    /// nothing to select or step onto.
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        let Some(pattern) = self.pattern.as_ref() else { return Vec::new() };
+
+        pattern
+            .find_iter(text)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+
+    /// Whether `selected` is exactly one match, as left by Next/Previous.
+    pub fn is_match(&self, selected: &str) -> bool {
+        self.pattern
+            .as_ref()
+            .and_then(|pattern| pattern.find(selected))
+            .map_or(false, |found| found.range() == (0..selected.len()))
+    }
+
+    /// The text a single match becomes, with `$1`-style groups expanded in
+    /// regex mode.
+    pub fn replacement_for(&self, matched: &str) -> String {
+        match self.pattern.as_ref() {
+            Some(pattern) if self.options.regex => pattern.replace(matched, self.replacement.as_str()).into_owned(),
+            _ => self.replacement.clone()
+        }
+    }
+
+    /// Replaces every occurrence at once, returning the new text and the
+    /// number of replacements. Matches are found in the original text only,
+    /// so a replacement containing the query can't be matched again. Empty
+    /// matches are skipped, as in `matches`, so the count agrees with the
+    /// find bar.
+    pub fn replace_all(&self, text: &str) -> (String, usize) {
+        let Some(pattern) = self.pattern.as_ref() else { return (text.to_owned(), 0) };
+
+        let mut replaced = String::with_capacity(text.len());
+        let mut end = 0;
+        let mut count = 0;
+
+        for captures in pattern.captures_iter(text) {
+            let Some(found) = captures.get(0).filter(|found| !found.range().is_empty()) else { continue };
+
+            replaced.push_str(&text[end..found.start()]);
+
+            if self.options.regex {
+                captures.expand(&self.replacement, &mut replaced);
+            } else {
+                replaced.push_str(&self.replacement);
+            }
+
+            end = found.end();
+            count += 1;
+        }
+
+        replaced.push_str(&text[end..]);
+
+        (replaced, count)
+    }
+
+    /// Like `replace_all`, but only inside `range`. Also returns where the
+    /// replaced part ended up, so it can be selected again.
+    pub fn replace_in(&self, text: &str, range: Range<usize>) -> (String, usize, Range<usize>) {
+        let (replaced, count) = self.replace_all(&text[range.clone()]);
+        let end = range.start + replaced.len();
+
+        (format!("{}{}{}", &text[..range.start], replaced, &text[range.end..]), count, range.start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, replacement: &str, regex: bool) -> Find {
+        let mut find = Find::new(Options { regex, ..Options::default() });
+        find.set_query(String::from(query));
+        find.replacement = String::from(replacement);
+        find
+    }
+
+    #[test]
+    fn empty_matches_are_neither_counted_nor_replaced() {
+        let find = find("^", "> ", true);
+
+        assert!(find.matches("a\nb").is_empty());
+        assert_eq!(find.replace_all("a\nb"), (String::from("a\nb"), 0));
+    }
+
+    #[test]
+    fn replace_all_agrees_with_the_match_count() {
+        let find = find(r"\d*", "#", true);
+        let text = "a1b22c";
+
+        let (replaced, count) = find.replace_all(text);
+
+        assert_eq!(count, find.matches(text).len());
+        assert_eq!(replaced, "a#b#c");
+    }
+
+    #[test]
+    fn regex_replacements_expand_groups_and_literal_ones_do_not() {
+        assert_eq!(find(r"(\w+)=(\w+)", "$2=$1", true).replace_all("a=b c=d"), (String::from("b=a d=c"), 2));
+        assert_eq!(find("a", "$1", false).replace_all("aba"), (String::from("$1b$1"), 2));
+    }
+}
@@ -0,0 +1,159 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{self, Tree, Widget};
+use iced::advanced::{Clipboard, Shell};
+use iced::{event, mouse, Color, Element, Event, Length, Rectangle};
+
+use crate::scroll::Metrics;
+
+/// Draws a faint vertical line for every indentation level over an editor
+/// laid out at its full size, so line `n` always sits `n` rows down.
+pub struct IndentGuides<'a, Message, Renderer> {
+    content: Element<'a, Message, Renderer>,
+    levels: Vec<usize>,
+    level_width: f32,
+    metrics: Metrics,
+    color: Color
+}
+
+impl<'a, Message, Renderer> IndentGuides<'a, Message, Renderer> {
+    pub fn new(
+        content: impl Into<Element<'a, Message, Renderer>>,
+        levels: Vec<usize>,
+        tab_width: usize,
+        metrics: Metrics,
+        color: Color
+    ) -> Self {
+        IndentGuides {
+            content: content.into(),
+            levels,
+            level_width: tab_width as f32 * metrics.advance,
+            metrics,
+            color
+        }
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for IndentGuides<'a, Message, Renderer>
+where
+    Renderer: renderer::Renderer
+{
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, tree: &mut Tree, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.as_widget().layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle
+    ) {
+        self.content.as_widget().draw(&tree.children[0], renderer, theme, style, layout, cursor, viewport);
+
+        let bounds = layout.bounds();
+        let Metrics { padding, line_height, .. } = self.metrics;
+
+        // Every line is one row, so the first one on screen is found
+        // without walking past all those above it.
+        let first = ((viewport.y - bounds.y - padding) / line_height).floor().max(0.0) as usize;
+
+        for (line, level) in self.levels.iter().enumerate().skip(first) {
+            let y = bounds.y + padding + line as f32 * line_height;
+
+            if y > viewport.y + viewport.height {
+                break;
+            }
+
+            for guide in 0..*level {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x: bounds.x + padding + guide as f32 * self.level_width,
+                            y,
+                            width: 1.0,
+                            height: line_height
+                        },
+                        border_radius: 0.0.into(),
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT
+                    },
+                    self.color
+                );
+            }
+        }
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(&tree.children[0], layout, cursor, viewport, renderer)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation<Message>
+    ) {
+        self.content.as_widget().operate(&mut tree.children[0], layout, renderer, operation);
+    }
+}
+
+impl<'a, Message, Renderer> From<IndentGuides<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Message: 'a,
+    Renderer: renderer::Renderer + 'a
+{
+    fn from(guides: IndentGuides<'a, Message, Renderer>) -> Self {
+        Element::new(guides)
+    }
+}
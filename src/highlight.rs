@@ -0,0 +1,147 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use iced::advanced::text::highlighter::{self as text_highlighter, Format};
+use iced::highlighter::{self, Highlighter};
+use iced::theme::Palette;
+use iced::Font;
+
+/// Syntax highlighting plus marked spans drawn in a color of their own,
+/// such as the bracket pair around the cursor or every search match.
+pub struct Marked {
+    inner: Highlighter,
+    settings: Settings
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub syntax: highlighter::Settings,
+    pub marks: Marks
+}
+
+/// Byte ranges to mark, grouped by line. Later marks win where they
+/// overlap.
+pub type Marks = BTreeMap<usize, Vec<(Range<usize>, Mark)>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Bracket,
+    Match
+}
+
+pub enum Highlight {
+    Syntax(highlighter::Highlight),
+    Mark(Mark)
+}
+
+impl Highlight {
+    pub fn to_format(&self, palette: &Palette) -> Format<Font> {
+        let color = match self {
+            Highlight::Syntax(highlight) => return highlight.to_format(),
+            Highlight::Mark(Mark::Bracket) => palette.primary,
+            Highlight::Mark(Mark::Match) => palette.danger
+        };
+
+        Format {
+            color: Some(color),
+            font: None
+        }
+    }
+}
+
+impl text_highlighter::Highlighter for Marked {
+    type Settings = Settings;
+    type Highlight = Highlight;
+    type Iterator<'a> = std::vec::IntoIter<(Range<usize>, Highlight)>;
+
+    fn new(settings: &Settings) -> Self {
+        Marked {
+            inner: Highlighter::new(&settings.syntax),
+            settings: settings.clone()
+        }
+    }
+
+    fn update(&mut self, new_settings: &Settings) {
+        if new_settings.syntax != self.settings.syntax {
+            self.inner.update(&new_settings.syntax);
+        } else {
+            // Only the lines whose marks came or went need redrawing.
+            if let Some(line) = first_change(&self.settings.marks, &new_settings.marks) {
+                self.inner.change_line(line);
+            }
+        }
+
+        self.settings = new_settings.clone();
+    }
+
+    fn change_line(&mut self, line: usize) {
+        self.inner.change_line(line);
+    }
+
+    fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
+        let index = self.inner.current_line();
+
+        let mut spans = self
+            .inner
+            .highlight_line(line)
+            .map(|(range, highlight)| (range, Highlight::Syntax(highlight)))
+            .collect::<Vec<_>>();
+
+        // Later spans win where they overlap.
+        spans.extend(
+            self.settings
+                .marks
+                .get(&index)
+                .into_iter()
+                .flatten()
+                .filter(|(range, _)| range.end <= line.len())
+                .map(|(range, mark)| (range.clone(), Highlight::Mark(*mark)))
+        );
+
+        spans.into_iter()
+    }
+
+    fn current_line(&self) -> usize {
+        self.inner.current_line()
+    }
+}
+
+/// The first line whose marks differ, walking both maps in step.
+fn first_change(old: &Marks, new: &Marks) -> Option<usize> {
+    let differing = old
+        .iter()
+        .zip(new)
+        .find(|(old, new)| old != new)
+        .map(|((old, _), (new, _))| *old.min(new));
+
+    differing.or_else(|| {
+        match old.len().cmp(&new.len()) {
+            Ordering::Less => new.keys().nth(old.len()),
+            Ordering::Greater => old.keys().nth(new.len()),
+            Ordering::Equal => None
+        }
+        .copied()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marks(lines: &[usize]) -> Marks {
+        lines.iter().map(|line| (*line, vec![(0..1, Mark::Match)])).collect()
+    }
+
+    #[test]
+    fn finds_the_first_line_whose_marks_changed() {
+        assert_eq!(first_change(&marks(&[1, 5, 9]), &marks(&[1, 5, 9])), None);
+        assert_eq!(first_change(&marks(&[1, 5, 9]), &marks(&[1, 6, 9])), Some(5));
+        assert_eq!(first_change(&marks(&[1, 5]), &marks(&[1, 5, 9])), Some(9));
+        assert_eq!(first_change(&marks(&[1, 5, 9]), &marks(&[5, 9])), Some(1));
+
+        let mut moved = marks(&[1, 5]);
+        moved.insert(5, vec![(2..3, Mark::Match)]);
+        assert_eq!(first_change(&marks(&[1, 5]), &moved), Some(5));
+    }
+}
@@ -0,0 +1,79 @@
+pub const DEFAULT_LIMIT: usize = 300;
+
+#[derive(Debug)]
+pub struct History {
+    undo_stack: Vec<String>,
+    redo_stack: Vec<String>,
+    coalescing: bool,
+    limit: usize
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::with_limit(DEFAULT_LIMIT)
+    }
+}
+
+impl History {
+    pub fn with_limit(limit: usize) -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalescing: false,
+            limit: limit.max(1)
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Remembers `text` as the state to return to before the next edit.
+    ///
+    /// Consecutive calls with `coalesce` set collapse into the first one, so
+    /// typing a word becomes a single undo step.
+    pub fn record(&mut self, text: String, coalesce: bool) {
+        if !(coalesce && self.coalescing) {
+            self.undo_stack.push(text);
+
+            if self.undo_stack.len() > self.limit {
+                self.undo_stack.remove(0);
+            }
+        }
+
+        self.coalescing = coalesce;
+        self.redo_stack.clear();
+    }
+
+    pub fn interrupt(&mut self) {
+        self.coalescing = false;
+    }
+
+    pub fn undo(&mut self, current: String) -> Option<String> {
+        let snapshot = self.undo_stack.pop()?;
+
+        self.redo_stack.push(current);
+        self.coalescing = false;
+
+        Some(snapshot)
+    }
+
+    pub fn redo(&mut self, current: String) -> Option<String> {
+        let snapshot = self.redo_stack.pop()?;
+
+        self.undo_stack.push(current);
+        self.coalescing = false;
+
+        Some(snapshot)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.coalescing = false;
+    }
+}
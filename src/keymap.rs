@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+
+use iced::keyboard::{KeyCode, Modifiers};
+
+use crate::lines::Direction;
+
+/// Everything a key can be bound to, by the name used in the settings file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortcut {
+    New,
+    Open,
+    Save,
+    SaveAs,
+    Reload,
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+    SelectAll,
+    AddCursor,
+    Duplicate,
+    DeleteLine,
+    JoinLines,
+    Reflow,
+    MatchingBracket,
+    InsertDate { utc: bool },
+    Evaluate { append: bool },
+    MoveLines(Direction),
+    Comment,
+    BlockComment,
+    TransposeChars,
+    TransposeWords,
+    StepNumber(i64),
+    Find,
+    FindNext,
+    FindPrevious,
+    GoTo,
+    Dismiss,
+    IncreaseFontSize,
+    DecreaseFontSize,
+    ResetFontSize,
+    CloseTab,
+    NextTab,
+    PreviousTab
+}
+
+impl Shortcut {
+    const ALL: &'static [(&'static str, Shortcut)] = &[
+        ("New", Shortcut::New),
+        ("Open", Shortcut::Open),
+        ("Save", Shortcut::Save),
+        ("SaveAs", Shortcut::SaveAs),
+        ("Reload", Shortcut::Reload),
+        ("Undo", Shortcut::Undo),
+        ("Redo", Shortcut::Redo),
+        ("Cut", Shortcut::Cut),
+        ("Copy", Shortcut::Copy),
+        ("Paste", Shortcut::Paste),
+        ("SelectAll", Shortcut::SelectAll),
+        ("AddCursor", Shortcut::AddCursor),
+        ("Duplicate", Shortcut::Duplicate),
+        ("DeleteLine", Shortcut::DeleteLine),
+        ("JoinLines", Shortcut::JoinLines),
+        ("Reflow", Shortcut::Reflow),
+        ("MatchingBracket", Shortcut::MatchingBracket),
+        ("InsertDate", Shortcut::InsertDate { utc: false }),
+        ("InsertDateUtc", Shortcut::InsertDate { utc: true }),
+        ("Evaluate", Shortcut::Evaluate { append: false }),
+        ("EvaluateAppend", Shortcut::Evaluate { append: true }),
+        ("MoveLinesUp", Shortcut::MoveLines(Direction::Up)),
+        ("MoveLinesDown", Shortcut::MoveLines(Direction::Down)),
+        ("Comment", Shortcut::Comment),
+        ("BlockComment", Shortcut::BlockComment),
+        ("TransposeChars", Shortcut::TransposeChars),
+        ("TransposeWords", Shortcut::TransposeWords),
+        ("Increment", Shortcut::StepNumber(1)),
+        ("Decrement", Shortcut::StepNumber(-1)),
+        ("IncrementBy10", Shortcut::StepNumber(10)),
+        ("DecrementBy10", Shortcut::StepNumber(-10)),
+        ("Find", Shortcut::Find),
+        ("FindNext", Shortcut::FindNext),
+        ("FindPrevious", Shortcut::FindPrevious),
+        ("GoTo", Shortcut::GoTo),
+        ("Dismiss", Shortcut::Dismiss),
+        ("IncreaseFontSize", Shortcut::IncreaseFontSize),
+        ("DecreaseFontSize", Shortcut::DecreaseFontSize),
+        ("ResetFontSize", Shortcut::ResetFontSize),
+        ("CloseTab", Shortcut::CloseTab),
+        ("NextTab", Shortcut::NextTab),
+        ("PreviousTab", Shortcut::PreviousTab)
+    ];
+
+    fn named(name: &str) -> Option<Shortcut> {
+        Shortcut::ALL
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|&(_, shortcut)| shortcut)
+    }
+}
+
+const DEFAULTS: &[(&str, &str)] = &[
+    ("Ctrl+N", "New"),
+    ("Ctrl+O", "Open"),
+    ("Ctrl+S", "Save"),
+    ("Ctrl+Shift+S", "SaveAs"),
+    ("Ctrl+R", "Reload"),
+    ("F5", "Reload"),
+    ("Ctrl+Z", "Undo"),
+    ("Ctrl+Shift+Z", "Redo"),
+    ("Ctrl+X", "Cut"),
+    ("Ctrl+C", "Copy"),
+    ("Ctrl+V", "Paste"),
+    ("Ctrl+A", "SelectAll"),
+    ("Ctrl+Alt+D", "AddCursor"),
+    ("Ctrl+D", "Duplicate"),
+    ("Ctrl+Shift+K", "DeleteLine"),
+    ("Ctrl+J", "JoinLines"),
+    ("Alt+Q", "Reflow"),
+    ("Ctrl+M", "MatchingBracket"),
+    ("Ctrl+;", "InsertDate"),
+    ("Ctrl+Shift+;", "InsertDateUtc"),
+    ("Ctrl+E", "Evaluate"),
+    ("Ctrl+Shift+E", "EvaluateAppend"),
+    ("Alt+Up", "MoveLinesUp"),
+    ("Alt+Down", "MoveLinesDown"),
+    ("Ctrl+/", "Comment"),
+    ("Ctrl+Shift+/", "BlockComment"),
+    ("Control+T", "TransposeChars"),
+    ("Alt+T", "TransposeWords"),
+    ("Ctrl+Up", "Increment"),
+    ("Ctrl+Down", "Decrement"),
+    ("Ctrl+Shift+Up", "IncrementBy10"),
+    ("Ctrl+Shift+Down", "DecrementBy10"),
+    ("Ctrl+F", "Find"),
+    ("F3", "FindNext"),
+    ("Shift+F3", "FindPrevious"),
+    ("Ctrl+G", "GoTo"),
+    ("Escape", "Dismiss"),
+    ("Ctrl+Plus", "IncreaseFontSize"),
+    ("Ctrl+=", "IncreaseFontSize"),
+    ("Ctrl+Shift+=", "IncreaseFontSize"),
+    ("Ctrl+NumpadAdd", "IncreaseFontSize"),
+    ("Ctrl+Minus", "DecreaseFontSize"),
+    ("Ctrl+NumpadSubtract", "DecreaseFontSize"),
+    ("Ctrl+0", "ResetFontSize"),
+    ("Ctrl+Numpad0", "ResetFontSize"),
+    ("Ctrl+W", "CloseTab"),
+    ("Control+Tab", "NextTab"),
+    ("Control+Shift+Tab", "PreviousTab")
+];
+
+pub struct Keymap {
+    bindings: HashMap<(KeyCode, Modifiers), Shortcut>,
+    // Every chord as it was written, defaults first, for showing in hints.
+    labels: Vec<((KeyCode, Modifiers), String)>
+}
+
+impl Keymap {
+    /// The default bindings with `overrides` from the settings file on top.
+    /// An override bound to an empty name removes the default. Entries that
+    /// don't parse are left out and returned so they can be reported.
+    pub fn new(overrides: &BTreeMap<String, String>) -> (Keymap, Vec<String>) {
+        let mut bindings = HashMap::new();
+        let mut labels = Vec::new();
+        let mut invalid = Vec::new();
+
+        for &(keys, name) in DEFAULTS {
+            if let (Some(chord), Some(shortcut)) = (parse_chord(keys), Shortcut::named(name)) {
+                bindings.insert(chord, shortcut);
+                labels.push((chord, keys.to_owned()));
+            }
+        }
+
+        for (keys, name) in overrides {
+            let Some(chord) = parse_chord(keys) else {
+                invalid.push(keys.clone());
+                continue;
+            };
+
+            if name.is_empty() {
+                bindings.remove(&chord);
+            } else if let Some(shortcut) = Shortcut::named(name) {
+                bindings.insert(chord, shortcut);
+                labels.push((chord, keys.clone()));
+            } else {
+                invalid.push(format!("{keys}: {name}"));
+            }
+        }
+
+        (Keymap { bindings, labels }, invalid)
+    }
+
+    pub fn get(&self, key_code: KeyCode, modifiers: Modifiers) -> Option<Shortcut> {
+        self.bindings.get(&(key_code, modifiers)).copied()
+    }
+
+    /// The first chord still bound to `shortcut`, as written.
+    pub fn label(&self, shortcut: Shortcut) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(chord, _)| self.bindings.get(chord) == Some(&shortcut))
+            .map(|(_, label)| label.as_str())
+    }
+}
+
+/// Reads a chord like `Ctrl+Shift+S`. `Ctrl` is the platform's main
+/// modifier, which is ⌘ on macOS, while `Control` always means the key
+/// labelled so.
+fn parse_chord(chord: &str) -> Option<(KeyCode, Modifiers)> {
+    let mut parts = chord.split('+').map(str::trim).collect::<Vec<_>>();
+
+    // `Ctrl++` splits into an empty part where the plus key was.
+    if chord.ends_with("++") {
+        parts.retain(|part| !part.is_empty());
+        parts.push("Plus");
+    }
+
+    let (key, modifiers) = parts.split_last()?;
+    let mut result = Modifiers::empty();
+
+    for modifier in modifiers {
+        result |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" => Modifiers::COMMAND,
+            "control" => Modifiers::CTRL,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            "logo" | "super" | "win" => Modifiers::LOGO,
+            _ => return None
+        };
+    }
+
+    Some((parse_key(key)?, result))
+}
+
+fn parse_key(key: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+        KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+        KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+        KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9
+    ];
+    const FUNCTION: [KeyCode; 12] = [
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12
+    ];
+
+    let lower = key.to_ascii_lowercase();
+
+    if let [c] = lower.as_bytes() {
+        match c {
+            b'a'..=b'z' => return Some(LETTERS[usize::from(c - b'a')]),
+            b'0'..=b'9' => return Some(DIGITS[usize::from(c - b'0')]),
+            _ => {}
+        }
+    }
+
+    if let Some(number) = lower.strip_prefix('f').and_then(|number| number.parse::<usize>().ok()) {
+        return number.checked_sub(1).and_then(|index| FUNCTION.get(index)).copied();
+    }
+
+    Some(match lower.as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "tab" => KeyCode::Tab,
+        "enter" | "return" => KeyCode::Enter,
+        "escape" | "esc" => KeyCode::Escape,
+        "space" => KeyCode::Space,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "/" | "slash" => KeyCode::Slash,
+        "\\" | "backslash" => KeyCode::Backslash,
+        "=" | "equals" => KeyCode::Equals,
+        "-" | "minus" => KeyCode::Minus,
+        "plus" => KeyCode::Plus,
+        "," | "comma" => KeyCode::Comma,
+        "." | "period" => KeyCode::Period,
+        ";" | "semicolon" => KeyCode::Semicolon,
+        "[" => KeyCode::LBracket,
+        "]" => KeyCode::RBracket,
+        "numpadadd" => KeyCode::NumpadAdd,
+        "numpadsubtract" => KeyCode::NumpadSubtract,
+        "numpad0" => KeyCode::Numpad0,
+        _ => return None
+    })
+}
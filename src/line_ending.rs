@@ -0,0 +1,77 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr
+}
+
+impl LineEnding {
+    pub const ALL: &[LineEnding] = &[LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r"
+        }
+    }
+
+    /// Returns the ending used by most lines, and whether the text mixes
+    /// several of them.
+    pub fn detect(text: &str) -> (LineEnding, bool) {
+        let crlf = text.matches("\r\n").count();
+        let cr = text.matches('\r').count() - crlf;
+        let lf = text.matches('\n').count() - crlf;
+
+        let dominant = if lf == 0 && crlf == 0 && cr == 0 {
+            LineEnding::default()
+        } else if crlf > lf && crlf >= cr {
+            LineEnding::Crlf
+        } else if cr > lf && cr > crlf {
+            LineEnding::Cr
+        } else {
+            LineEnding::Lf
+        };
+
+        let styles = [lf, crlf, cr].into_iter().filter(|count| *count > 0).count();
+
+        (dominant, styles > 1)
+    }
+
+    /// The editor always works with `\n`; the original ending is only put
+    /// back when the text is written out.
+    pub fn normalize(text: &str) -> String {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => LineEnding::normalize(text),
+            LineEnding::Crlf | LineEnding::Cr => LineEnding::normalize(text).replace('\n', self.as_str())
+        }
+    }
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        if cfg!(windows) {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::Crlf => write!(f, "CRLF"),
+            LineEnding::Cr => write!(f, "CR")
+        }
+    }
+}
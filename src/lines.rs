@@ -0,0 +1,459 @@
+use std::cmp::Ordering;
+
+use crate::file::{FinalNewline, SaveOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Ascending,
+    Descending,
+    CaseInsensitive,
+    // Runs of digits compare as numbers, so `file2` comes before `file10`.
+    Natural
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    Title
+}
+
+/// Maps `text` to `case` with the full Unicode tables, so `ß` becomes `SS`
+/// and Cyrillic converts as well as Latin. Title case capitalizes the first
+/// letter of every word and lowers the rest.
+pub fn change_case(text: &str, case: Case) -> String {
+    match case {
+        Case::Upper => text.to_uppercase(),
+        Case::Lower => text.to_lowercase(),
+        Case::Title => {
+            let mut result = String::with_capacity(text.len());
+            let mut in_word = false;
+
+            for c in text.chars() {
+                if in_word {
+                    result.extend(c.to_lowercase());
+                } else {
+                    result.extend(c.to_uppercase());
+                }
+
+                in_word = c.is_alphanumeric() || c == '\'';
+            }
+
+            result
+        }
+    }
+}
+
+/// `start`, `start + step`, … zero-padded to `width` digits, with the minus
+/// sign in front of the padding.
+pub fn sequence(start: i64, step: i64, width: usize) -> impl Iterator<Item = String> {
+    (0..).map_while(move |index: i64| start.checked_add(step.checked_mul(index)?)).map(move |value| {
+        if value < 0 {
+            format!("-{:0width$}", value.unsigned_abs())
+        } else {
+            format!("{value:0width$}")
+        }
+    })
+}
+
+/// Splits `text` into lines, lets `f` rearrange them and joins them back.
+///
+/// Splitting on `\n` keeps a trailing empty line when the text ends with a
+/// newline, so joining gives back exactly what was there.
+pub fn lines_mut<T>(text: &str, f: impl FnOnce(&mut Vec<String>) -> T) -> (String, T) {
+    let mut lines = text.split('\n').map(str::to_owned).collect::<Vec<_>>();
+    let result = f(&mut lines);
+
+    (lines.join("\n"), result)
+}
+
+/// What saving with `options` makes of `text`. Trimming runs first, so the
+/// final newlines are counted on the trimmed text, and it can leave
+/// `cursor_line` alone. An empty file stays empty rather than becoming a
+/// lone newline.
+pub fn prepare_save(text: &str, options: &SaveOptions, cursor_line: usize) -> String {
+    let mut text = text.to_owned();
+
+    if options.trim_on_save {
+        (text, _) = lines_mut(&text, |lines| {
+            for (index, line) in lines.iter_mut().enumerate() {
+                if options.trim_skips_cursor_line && index == cursor_line {
+                    continue;
+                }
+
+                line.truncate(line.trim_end_matches([' ', '\t']).len());
+            }
+        });
+    }
+
+    if !text.is_empty() {
+        match options.final_newline {
+            FinalNewline::Keep => {},
+            FinalNewline::Ensure => if !text.ends_with('\n') {
+                text.push('\n');
+            },
+            FinalNewline::Single => {
+                text.truncate(text.trim_end_matches('\n').len());
+                text.push('\n');
+            }
+        }
+    }
+
+    text
+}
+
+/// Re-encodes the leading whitespace of `line` with tabs or spaces. Mixed
+/// indentation is first expanded to columns, and nothing after the
+/// indentation is touched.
+pub fn reindent(line: &str, width: usize, tabs: bool) -> String {
+    let width = width.max(1);
+    let (indent, rest) = line.split_at(line.len() - line.trim_start_matches([' ', '\t']).len());
+    let columns = indent_columns(indent, width);
+
+    let indent = if tabs {
+        "\t".repeat(columns / width) + &" ".repeat(columns % width)
+    } else {
+        " ".repeat(columns)
+    };
+
+    indent + rest
+}
+
+/// Removes up to one indentation level of `width` columns from the start of
+/// `line`. A tab counts as reaching the next stop, so mixed indentation loses
+/// exactly one level and the text itself is never touched.
+pub fn dedent(line: &str, width: usize) -> &str {
+    let width = width.max(1);
+    let mut column = 0;
+    let mut end = 0;
+
+    for c in line.chars() {
+        match c {
+            ' ' if column < width => column += 1,
+            '\t' if column < width => column = width,
+            _ => break
+        }
+        end += 1;
+    }
+
+    &line[end..]
+}
+
+/// How many columns the leading whitespace of `line` spans, with tabs
+/// stopping at multiples of `width`.
+pub fn indent_columns(line: &str, width: usize) -> usize {
+    columns(&line[..line.len() - line.trim_start_matches([' ', '\t']).len()], width)
+}
+
+/// How many columns `text` spans on screen, with tabs stopping at multiples
+/// of `width`.
+pub fn columns(text: &str, width: usize) -> usize {
+    let width = width.max(1);
+
+    text.chars()
+        .fold(0, |column, c| if c == '\t' { (column / width + 1) * width } else { column + 1 })
+}
+
+/// Replaces every tab with the spaces up to the next tab stop, so the text
+/// lines up the way it does in the editor.
+pub fn expand_tabs(text: &str, width: usize) -> String {
+    let width = width.max(1);
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+
+    for c in text.chars() {
+        if c == '\t' {
+            let next = (column / width + 1) * width;
+            expanded.extend(std::iter::repeat(' ').take(next - column));
+            column = next;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+
+    expanded
+}
+
+/// Pads lines with spaces so the first `separator` in each one starts at
+/// the same column. Lines without it are left alone. Returns how many lines
+/// had to move.
+pub fn align(lines: &mut [String], separator: &str, width: usize) -> usize {
+    if separator.is_empty() {
+        return 0;
+    }
+
+    let found = lines
+        .iter()
+        .map(|line| line.find(separator).map(|index| (index, columns(&line[..index], width))))
+        .collect::<Vec<_>>();
+
+    let Some(target) = found.iter().flatten().map(|&(_, column)| column).max() else { return 0 };
+    let mut moved = 0;
+
+    for (line, found) in lines.iter_mut().zip(found) {
+        if let Some((index, column)) = found.filter(|&(_, column)| column < target) {
+            line.insert_str(index, &" ".repeat(target - column));
+            moved += 1;
+        }
+    }
+
+    moved
+}
+
+/// The indentation and comment or quote markers at the start of `line`,
+/// such as `    // ` or `> `.
+fn prefix(line: &str) -> &str {
+    let rest = line.trim_start_matches([' ', '\t', '/', '#', '>', ';', '!']);
+    &line[..line.len() - rest.len()]
+}
+
+/// Re-wraps `lines` so none runs past `column` where it can be helped.
+/// The prefix all of them share is kept on every line, lines with nothing
+/// but a prefix split paragraphs that are wrapped on their own, and a word
+/// too long to fit gets a line to itself instead of being broken.
+pub fn reflow(lines: &mut Vec<String>, column: usize, width: usize) {
+    let is_blank = |line: &str| prefix(line).len() >= line.trim_end().len();
+
+    let common = lines
+        .iter()
+        .filter(|line| !is_blank(line.as_str()))
+        .map(|line| prefix(line))
+        .reduce(|common, next| &common[..common.bytes().zip(next.bytes()).take_while(|(a, b)| a == b).count()])
+        .unwrap_or_default()
+        .to_owned();
+
+    let limit = column.saturating_sub(columns(&common, width)).max(1);
+    let mut result = Vec::with_capacity(lines.len());
+    let mut words = Vec::new();
+
+    let flush = |words: &mut Vec<&str>, result: &mut Vec<String>| {
+        let mut line = String::new();
+
+        for word in words.drain(..) {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > limit {
+                result.push(format!("{common}{line}"));
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(word);
+        }
+
+        if !line.is_empty() {
+            result.push(format!("{common}{line}"));
+        }
+    };
+
+    for line in lines.iter() {
+        if is_blank(line) {
+            flush(&mut words, &mut result);
+            result.push(line.clone());
+        } else {
+            words.extend(line[common.len()..].split_whitespace());
+        }
+    }
+
+    flush(&mut words, &mut result);
+    *lines = result;
+}
+
+/// The indentation level of every line. A blank line takes the shallower of
+/// its neighbours, so guides run through gaps inside a block but don't stick
+/// out past it.
+pub fn indent_levels(text: &str, width: usize) -> Vec<usize> {
+    let width = width.max(1);
+
+    let levels = text
+        .split('\n')
+        .map(|line| (!line.trim().is_empty()).then(|| indent_columns(line, width) / width))
+        .collect::<Vec<_>>();
+
+    let mut following = 0;
+    let mut next = vec![0; levels.len()];
+
+    for (index, level) in levels.iter().enumerate().rev() {
+        if let Some(level) = level {
+            following = *level;
+        }
+        next[index] = following;
+    }
+
+    let mut previous = 0;
+
+    levels
+        .iter()
+        .zip(next)
+        .map(|(level, next)| match level {
+            Some(level) => {
+                previous = *level;
+                *level
+            },
+            None => previous.min(next)
+        })
+        .collect()
+}
+
+pub fn sort(lines: &mut [String], sort: Sort) {
+    match sort {
+        Sort::Ascending => lines.sort(),
+        Sort::Descending => lines.sort_by(|a, b| b.cmp(a)),
+        Sort::CaseInsensitive => lines.sort_by_cached_key(|line| line.to_lowercase()),
+        Sort::Natural => lines.sort_by(|a, b| natural_cmp(a, b))
+    }
+}
+
+/// Removes repeated lines in place and returns how many were removed.
+pub fn unique(lines: &mut Vec<String>, adjacent: bool, ignore_trailing_whitespace: bool) -> usize {
+    let key = |line: &String| {
+        if ignore_trailing_whitespace {
+            line.trim_end_matches([' ', '\t']).to_owned()
+        } else {
+            line.clone()
+        }
+    };
+
+    let before = lines.len();
+
+    if adjacent {
+        lines.dedup_by(|line, previous| key(&*line) == key(&*previous));
+    } else {
+        let mut seen = std::collections::HashSet::new();
+        lines.retain(|line| seen.insert(key(line)));
+    }
+
+    before - lines.len()
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+
+                match x.len().cmp(&y.len()).then_with(|| x.cmp(y)) {
+                    Ordering::Equal => {},
+                    ordering => return ordering
+                }
+            },
+            (Some(x), Some(y)) => match x.cmp(&y) {
+                Ordering::Equal => {
+                    a.next();
+                    b.next();
+                },
+                ordering => return ordering
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut number = String::new();
+
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(trim_on_save: bool, final_newline: FinalNewline) -> SaveOptions {
+        SaveOptions {
+            trim_on_save,
+            trim_skips_cursor_line: false,
+            final_newline,
+            ..SaveOptions::default()
+        }
+    }
+
+    #[test]
+    fn an_empty_file_stays_empty() {
+        for final_newline in FinalNewline::ALL {
+            assert_eq!(prepare_save("", &options(true, *final_newline), 0), "");
+        }
+    }
+
+    #[test]
+    fn ensure_adds_a_newline_only_when_missing() {
+        let ensure = options(false, FinalNewline::Ensure);
+
+        assert_eq!(prepare_save("a", &ensure, 0), "a\n");
+        assert_eq!(prepare_save("a\n", &ensure, 0), "a\n");
+        assert_eq!(prepare_save("a\n\n\n", &ensure, 0), "a\n\n\n");
+    }
+
+    #[test]
+    fn single_collapses_trailing_blank_lines() {
+        let single = options(false, FinalNewline::Single);
+
+        assert_eq!(prepare_save("a", &single, 0), "a\n");
+        assert_eq!(prepare_save("a\n", &single, 0), "a\n");
+        assert_eq!(prepare_save("a\n\n\n", &single, 0), "a\n");
+    }
+
+    #[test]
+    fn keep_leaves_the_end_alone() {
+        let keep = options(false, FinalNewline::Keep);
+
+        assert_eq!(prepare_save("a", &keep, 0), "a");
+        assert_eq!(prepare_save("a\n\n", &keep, 0), "a\n\n");
+    }
+
+    #[test]
+    fn trimming_runs_before_the_newlines_are_counted() {
+        let single = options(true, FinalNewline::Single);
+
+        assert_eq!(prepare_save("a  \n \t\n\n", &single, 0), "a\n");
+    }
+
+    #[test]
+    fn trimming_can_skip_the_cursor_line() {
+        let options = SaveOptions {
+            trim_on_save: true,
+            ..SaveOptions::default()
+        };
+
+        assert_eq!(prepare_save("a \nb \n", &options, 1), "a\nb \n");
+    }
+
+    #[test]
+    fn blank_lines_take_the_shallower_neighbour() {
+        let text = "fn main() {\n    if x {\n        a();\n\n        b();\n    }\n\n}";
+
+        assert_eq!(indent_levels(text, 4), vec![0, 1, 2, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn tabs_count_as_a_full_level() {
+        assert_eq!(indent_levels("a\n\tb\n  \tc\n  d", 4), vec![0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_stop() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tc\td", 4), "ab  c   d");
+        assert_eq!(expand_tabs("абв\tг", 4), "абв г");
+        assert_eq!(columns("ab\tc\td", 4), expand_tabs("ab\tc\td", 4).chars().count());
+    }
+}
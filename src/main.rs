@@ -1,160 +1,2632 @@
-use std::{env, io};
-use std::path::{Path, PathBuf};
+mod brackets;
+mod calc;
+mod comment;
+mod cursor;
+mod date;
+mod document;
+mod encoding;
+mod export;
+mod file;
+mod find;
+mod guides;
+mod highlight;
+mod history;
+mod keymap;
+mod line_ending;
+mod lines;
+mod number;
+mod scroll;
+mod settings;
+mod transpose;
+mod watch;
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use iced::{executor, keyboard, theme, Application, Command, Element, Font, Length, Settings, Theme};
-use iced::widget::{button, column, container, horizontal_space, row, text, text_editor, tooltip, Text};
-use iced::highlighter::{self, Highlighter};
+use iced::{alignment, clipboard, event, executor, keyboard, subscription, theme, time, window, Alignment, Application, Color, Command, Element, Font, Length, Rectangle, Settings, Theme};
+use iced::widget::{button, checkbox, column, container, horizontal_space, pick_list, progress_bar, row, scrollable, text, text_editor, text_input, tooltip, Row, Text};
+use iced::highlighter;
 
-#[derive(Debug, Clone)]
-enum Error {
-    DialogClosed,
-    IOFailed(io::ErrorKind)
-}
+use document::Document;
+use file::{load_binary, load_file, open_file, pick_file, preview_file, reopen_file, save_file, save_file_as, Error, FinalNewline, Format, Loaded, Saved, Stamp};
+use find::Find;
+use guides::IndentGuides;
+use keymap::{Keymap, Shortcut};
+use history::History;
+use lines::{Case, Direction, Sort};
+use line_ending::LineEnding;
+use scroll::{Metrics, Visible};
+use settings::{EditorConfig, ThemeKind, DEFAULT_FONT_SIZE, MAX_FONT_SIZE, MAX_RECENT, MIN_FONT_SIZE};
 
 struct Editor {
-    content: text_editor::Content,
-    error: Option<Error>,
-    path: Option<PathBuf>,
-    is_dirty: bool
+    documents: Vec<Document>,
+    active: usize,
+    next_id: usize,
+    find: Option<Find>,
+    go_to: Option<GoTo>,
+    sequence: Option<Sequence>,
+    // What the lines are being aligned on while the align bar is open.
+    align: Option<String>,
+    confirm: Option<PendingAction>,
+    after_save: Option<PendingAction>,
+    conflict: Option<usize>,
+    binary: Option<PathBuf>,
+    too_large: Option<(PathBuf, u64)>,
+    loading: Option<Loading>,
+    split: Option<Split>,
+    // Matches of the find bar in the active document, with the document id,
+    // revision, query and options they were found for. Both the highlighter
+    // and the counter need them on every frame, so `update` finds them again
+    // only when one of those changes.
+    matches: Option<(MatchKey, Vec<Range<usize>>)>,
+    // What each pane last showed, for keeping the cursor in view while the
+    // editor is scrolled from outside. Indexed by `Pane`.
+    views: [Option<Rectangle>; 2],
+    config: EditorConfig,
+    // Settings are written one save at a time. Changes made meanwhile are
+    // saved once it lands, so the newest settings are the ones kept.
+    config_saving: bool,
+    config_queued: bool,
+    quitting: bool,
+    // iced only accepts 'static family names, so a configured name is
+    // leaked once when it is chosen rather than on every frame.
+    font_name: Option<&'static str>,
+    modifiers: keyboard::Modifiers,
+    keymap: Keymap
+}
+
+type MatchKey = (usize, u64, String, find::Options);
+
+#[derive(Debug)]
+struct Sequence {
+    start: String,
+    step: String,
+    width: String
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence {
+            start: String::from("1"),
+            step: String::from("1"),
+            width: String::new()
+        }
+    }
+}
+
+impl Sequence {
+    /// An empty width means no padding.
+    fn parse(&self) -> Option<impl Iterator<Item = String>> {
+        let start = self.start.trim().parse::<i64>().ok()?;
+        let step = self.step.trim().parse::<i64>().ok()?;
+        let width = match self.width.trim() {
+            "" => 0,
+            width => width.parse::<usize>().ok().filter(|width| *width <= 32)?
+        };
+
+        Some(lines::sequence(start, step, width))
+    }
+}
+
+#[derive(Debug, Default)]
+struct GoTo {
+    input: String,
+    invalid: bool
+}
+
+impl GoTo {
+    /// Accepts `line` or `line:column`, both counted from 1.
+    fn parse(&self) -> Option<(usize, Option<usize>)> {
+        let mut parts = self.input.trim().splitn(2, ':');
+
+        let line = parts.next()?.trim().parse::<usize>().ok().filter(|line| *line > 0)?;
+        let column = match parts.next() {
+            Some(column) => Some(column.trim().parse::<usize>().ok().filter(|column| *column > 0)?),
+            None => None
+        };
+
+        Some((line, column))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Left,
+    Right
+}
+
+/// A second document shown next to the active one. Both editors see every
+/// key press, so only the pane clicked last acts on them.
+#[derive(Debug, Clone, Copy)]
+struct Split {
+    id: usize,
+    focus: Pane
+}
+
+struct Loading {
+    id: usize,
+    from: usize,
+    path: PathBuf,
+    size: u64,
+    progress: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditCommand {
+    Cut,
+    Copy,
+    Paste,
+    SelectAll,
+    DeleteLine,
+    BlockComment,
+    TabsToSpaces,
+    SpacesToTabs,
+    Sort(Sort),
+    Unique,
+    UniqueAdjacent,
+    Reverse,
+    Case(Case),
+    Sequence,
+    Align,
+    Reflow,
+    Date { utc: bool },
+    Evaluate { append: bool }
+}
+
+impl EditCommand {
+    const ALL: &'static [EditCommand] = &[
+        EditCommand::Cut,
+        EditCommand::Copy,
+        EditCommand::Paste,
+        EditCommand::SelectAll,
+        EditCommand::DeleteLine,
+        EditCommand::BlockComment,
+        EditCommand::TabsToSpaces,
+        EditCommand::SpacesToTabs,
+        EditCommand::Sort(Sort::Ascending),
+        EditCommand::Sort(Sort::Descending),
+        EditCommand::Sort(Sort::CaseInsensitive),
+        EditCommand::Sort(Sort::Natural),
+        EditCommand::Unique,
+        EditCommand::UniqueAdjacent,
+        EditCommand::Reverse,
+        EditCommand::Case(Case::Upper),
+        EditCommand::Case(Case::Lower),
+        EditCommand::Case(Case::Title),
+        EditCommand::Sequence,
+        EditCommand::Align,
+        EditCommand::Reflow,
+        EditCommand::Date { utc: false },
+        EditCommand::Date { utc: true },
+        EditCommand::Evaluate { append: false },
+        EditCommand::Evaluate { append: true }
+    ];
+
+    fn message(self) -> Message {
+        match self {
+            EditCommand::Cut => Message::Cut,
+            EditCommand::Copy => Message::Copy,
+            EditCommand::Paste => Message::Paste,
+            EditCommand::SelectAll => Message::SelectAll,
+            EditCommand::DeleteLine => Message::DeleteLine,
+            EditCommand::BlockComment => Message::ToggleBlockComment,
+            EditCommand::TabsToSpaces => Message::TabsToSpaces,
+            EditCommand::SpacesToTabs => Message::SpacesToTabs,
+            EditCommand::Sort(sort) => Message::SortLines(sort),
+            EditCommand::Unique => Message::UniqueLines { adjacent: false },
+            EditCommand::UniqueAdjacent => Message::UniqueLines { adjacent: true },
+            EditCommand::Reverse => Message::ReverseLines,
+            EditCommand::Case(case) => Message::ChangeCase(case),
+            EditCommand::Sequence => Message::ToggleSequence,
+            EditCommand::Align => Message::ToggleAlign,
+            EditCommand::Reflow => Message::Reflow,
+            EditCommand::Date { utc } => Message::InsertDate { utc },
+            EditCommand::Evaluate { append } => Message::Evaluate { append }
+        }
+    }
+}
+
+impl std::fmt::Display for EditCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EditCommand::Cut => "Вырезать",
+            EditCommand::Copy => "Копировать",
+            EditCommand::Paste => "Вставить",
+            EditCommand::SelectAll => "Выделить всё",
+            EditCommand::DeleteLine => "Удалить строку",
+            EditCommand::BlockComment => "Блочный комментарий",
+            EditCommand::TabsToSpaces => "Отступы табами → пробелами",
+            EditCommand::SpacesToTabs => "Отступы пробелами → табами",
+            EditCommand::Sort(Sort::Ascending) => "Сортировать строки",
+            EditCommand::Sort(Sort::Descending) => "Сортировать по убыванию",
+            EditCommand::Sort(Sort::CaseInsensitive) => "Сортировать без учёта регистра",
+            EditCommand::Sort(Sort::Natural) => "Сортировать с числами по значению",
+            EditCommand::Unique => "Удалить повторяющиеся строки",
+            EditCommand::UniqueAdjacent => "Удалить соседние повторы",
+            EditCommand::Reverse => "Обратный порядок строк",
+            EditCommand::Case(Case::Upper) => "ВЕРХНИЙ РЕГИСТР",
+            EditCommand::Case(Case::Lower) => "нижний регистр",
+            EditCommand::Case(Case::Title) => "Каждое Слово С Заглавной",
+            EditCommand::Sequence => "Вставить последовательность…",
+            EditCommand::Align => "Выровнять по символу…",
+            EditCommand::Reflow => "Переформатировать абзац",
+            EditCommand::Date { utc: false } => "Вставить дату и время",
+            EditCommand::Date { utc: true } => "Вставить дату и время (UTC)",
+            EditCommand::Evaluate { append: false } => "Вычислить выражение",
+            EditCommand::Evaluate { append: true } => "Дописать результат выражения"
+        })
+    }
+}
+
+fn shortcut_message(shortcut: Shortcut) -> Message {
+    match shortcut {
+        Shortcut::New => Message::New,
+        Shortcut::Open => Message::Open,
+        Shortcut::Save => Message::Save,
+        Shortcut::SaveAs => Message::SaveAs,
+        Shortcut::Reload => Message::Reload,
+        Shortcut::Undo => Message::Undo,
+        Shortcut::Redo => Message::Redo,
+        Shortcut::Cut => Message::Cut,
+        Shortcut::Copy => Message::Copy,
+        Shortcut::Paste => Message::Paste,
+        Shortcut::SelectAll => Message::SelectAll,
+        Shortcut::AddCursor => Message::AddCursor,
+        Shortcut::Duplicate => Message::DuplicateLineOrSelection,
+        Shortcut::DeleteLine => Message::DeleteLine,
+        Shortcut::JoinLines => Message::JoinLines,
+        Shortcut::Reflow => Message::Reflow,
+        Shortcut::MatchingBracket => Message::JumpToMatchingBracket,
+        Shortcut::InsertDate { utc } => Message::InsertDate { utc },
+        Shortcut::Evaluate { append } => Message::Evaluate { append },
+        Shortcut::MoveLines(direction) => Message::MoveLines(direction),
+        Shortcut::Comment => Message::ToggleComment,
+        Shortcut::BlockComment => Message::ToggleBlockComment,
+        Shortcut::TransposeChars => Message::TransposeChars,
+        Shortcut::TransposeWords => Message::TransposeWords,
+        Shortcut::StepNumber(delta) => Message::StepNumber(delta),
+        Shortcut::Find => Message::ToggleFind,
+        Shortcut::FindNext => Message::FindNext,
+        Shortcut::FindPrevious => Message::FindPrevious,
+        Shortcut::GoTo => Message::ToggleGoTo,
+        Shortcut::Dismiss => Message::Dismiss,
+        Shortcut::IncreaseFontSize => Message::IncreaseFontSize,
+        Shortcut::DecreaseFontSize => Message::DecreaseFontSize,
+        Shortcut::ResetFontSize => Message::ResetFontSize,
+        Shortcut::CloseTab => Message::CloseActiveTab,
+        Shortcut::NextTab => Message::NextTab,
+        Shortcut::PreviousTab => Message::PreviousTab
+    }
+}
+
+// Past this many, matches are counted but no longer colored, so a query
+// like `e` in a large file doesn't slow down every frame.
+const MAX_MARKED_MATCHES: usize = 10_000;
+
+const TAB_WIDTHS: &[usize] = &[2, 4, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IndentStyle {
+    spaces: bool,
+    width: usize
+}
+
+impl IndentStyle {
+    fn all() -> Vec<IndentStyle> {
+        [true, false]
+            .into_iter()
+            .flat_map(|spaces| TAB_WIDTHS.iter().map(move |&width| IndentStyle { spaces, width }))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.spaces {
+            write!(f, "Пробелы: {}", self.width)
+        } else {
+            write!(f, "Табуляция: {}", self.width)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecentFile(PathBuf);
+
+impl std::fmt::Display for RecentFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.display())
+    }
+}
+
+struct Flags {
+    files: Vec<PathBuf>,
+    stdin: Option<(String, LineEnding)>,
+    config: EditorConfig
 }
 
+// Display names for the syntax picker, with the extension syntect knows
+// each language by.
+const SYNTAXES: &[(&str, &str)] = &[
+    ("Plain text", "txt"),
+    ("Rust", "rs"),
+    ("Python", "py"),
+    ("JavaScript", "js"),
+    ("C", "c"),
+    ("C++", "cpp"),
+    ("Java", "java"),
+    ("Go", "go"),
+    ("HTML", "html"),
+    ("CSS", "css"),
+    ("JSON", "json"),
+    ("Markdown", "md"),
+    ("Shell", "sh"),
+    ("SQL", "sql"),
+    ("YAML", "yaml"),
+    ("XML", "xml")
+];
+
 #[derive(Debug, Clone)]
 enum Message {
     Edit(text_editor::Action),
-    FileOpened(Result<(PathBuf, Arc<String>), Error>),
+    EditRight(text_editor::Action),
+    Viewport(Pane, Rectangle),
+    ToggleSplit,
+    ExportHtml,
+    Print,
+    Exported(usize, Result<PathBuf, Error>),
+    ModifiersChanged(keyboard::Modifiers),
+    FileOpened(usize, Result<Loaded, Error>),
+    OpenRecent(PathBuf),
+    FileDropped(PathBuf),
+    RecentOpened(usize, PathBuf, Result<Loaded, Error>),
+    FileReloaded(usize, Result<Loaded, Error>),
+    OpenBinary(PathBuf),
+    OpenLarge(PathBuf, u64),
+    PreviewLarge(PathBuf),
+    LoadProgress(f32),
+    LoadFinished(Result<Loaded, Error>),
+    CancelLoad,
+    CancelOpen,
     Open, 
     New,
     Save,
-    FileSave(Result<PathBuf, Error>)
+    SaveAs,
+    Reload,
+    FileSave(usize, Result<Saved, Error>),
+    SaveChecked(usize, Option<Stamp>),
+    FileChangedOnDisk(PathBuf),
+    DiskChecked(usize, Option<Stamp>),
+    ReloadChanged,
+    KeepChanged,
+    ResolveConflict(Resolution),
+    ToggleBackup(bool),
+    ToggleTrimOnSave(bool),
+    SetFinalNewline(FinalNewline),
+    ToggleAutoSave(bool),
+    ToggleAutoIndent(bool),
+    ToggleAutoClose(bool),
+    AutoSaveTick,
+    ToggleLineNumbers,
+    ToggleIndentGuides,
+    ToggleWordWrap,
+    ToggleTheme,
+    IncreaseFontSize,
+    DecreaseFontSize,
+    ResetFontSize,
+    ToggleReadOnly,
+    ConfirmDiscard(PendingAction, Choice),
+    SaveWithEncoding(Format),
+    ReopenWithEncoding(Format),
+    SetLineEnding(LineEnding),
+    NormalizeLineEndings,
+    SetBom(bool),
+    SyntaxSelected(String),
+    ConfigSaved(Result<(), std::io::ErrorKind>),
+    SelectTab(usize),
+    NextTab,
+    PreviousTab,
+    CloseTab(usize),
+    CloseActiveTab,
+    CloseRequested,
+    Undo,
+    Redo,
+    SelectAll,
+    DuplicateLineOrSelection,
+    TabsToSpaces,
+    SpacesToTabs,
+    SetIndentStyle(IndentStyle),
+    ToggleComment,
+    ToggleBlockComment,
+    MoveLines(Direction),
+    DeleteLine,
+    JoinLines,
+    TransposeChars,
+    TransposeWords,
+    Reflow,
+    JumpToMatchingBracket,
+    InsertDate { utc: bool },
+    Evaluate { append: bool },
+    StepNumber(i64),
+    KeyPressed(keyboard::KeyCode, keyboard::Modifiers),
+    AddCursor,
+    SortLines(Sort),
+    UniqueLines { adjacent: bool },
+    ReverseLines,
+    ChangeCase(Case),
+    Cut,
+    Copy,
+    Paste,
+    Pasted(Option<String>),
+    ToggleGoTo,
+    ToggleSequence,
+    SequenceStartChanged(String),
+    SequenceStepChanged(String),
+    SequenceWidthChanged(String),
+    InsertSequence,
+    ToggleAlign,
+    AlignChanged(String),
+    AlignLines,
+    GoToChanged(String),
+    GoToSubmitted,
+    GoToLine(usize, Option<usize>),
+    ToggleFind,
+    Dismiss,
+    FindChanged(String),
+    ToggleRegex(bool),
+    ToggleMatchCase(bool),
+    ToggleWholeWord(bool),
+    ToggleInSelection(bool),
+    ReplaceChanged(String),
+    FindNext,
+    FindPrevious,
+    Replace,
+    ReplaceAll,
+    Replaced(usize, u64, String, usize, Option<Range<usize>>)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PendingAction {
+    Close(usize),
+    Reload(usize),
+    Reopen(usize, Format),
+    Exit(usize)
+}
+
+impl PendingAction {
+    fn id(self) -> usize {
+        match self {
+            PendingAction::Close(id) | PendingAction::Reload(id) | PendingAction::Reopen(id, _) | PendingAction::Exit(id) => id
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Choice {
+    Save,
+    Discard,
+    Cancel
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Resolution {
+    Overwrite,
+    Reload,
+    Cancel
 }
 
-impl Application for Editor {
-    type Message = Message;
-    type Theme = Theme;
-    type Executor = executor::Default;
-    type Flags = ();
-
-    fn new(_flags: Self::Flags) -> (Editor, Command<Message>) {
-        (
-            Editor {
-                path: None,
-                content: text_editor::Content::with(""),
-                error: None,
-                is_dirty: true
-            },
-            Command::perform(
-                load_file(default_file()),
-                Message::FileOpened
-                )
-            )
-    }
+impl Editor {
+    /// The document in the pane with focus, which edits and commands act
+    /// on. That is the active tab unless the right pane was clicked last.
+    fn document(&self) -> &Document {
+        &self.documents[self.focused()]
+    }
+
+    fn document_mut(&mut self) -> &mut Document {
+        let index = self.focused();
+        &mut self.documents[index]
+    }
+
+    fn focused(&self) -> usize {
+        self.split
+            .filter(|split| split.focus == Pane::Right)
+            .and_then(|split| self.index_of(split.id))
+            .unwrap_or(self.active)
+    }
+
+    /// Shows the document at `index` in the left pane and gives it focus.
+    fn activate(&mut self, index: usize) {
+        self.active = index;
+
+        if let Some(split) = self.split.as_mut() {
+            split.focus = Pane::Left;
+        }
+    }
+
+    fn index_of(&self, id: usize) -> Option<usize> {
+        self.documents.iter().position(|document| document.id == id)
+    }
+
+    fn get(&self, id: usize) -> Option<&Document> {
+        self.documents.iter().find(|document| document.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Document> {
+        self.documents.iter_mut().find(|document| document.id == id)
+    }
+
+    fn add_document(&mut self, path: Option<PathBuf>) -> &mut Document {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut document = Document::new(id, path, self.config.line_ending);
+        document.history = History::with_limit(self.config.history_depth);
+
+        self.documents.push(document);
+        self.activate(self.documents.len() - 1);
+
+        self.document_mut()
+    }
+
+    fn guard_discard(&mut self, pending: PendingAction) -> Command<Message> {
+        let id = pending.id();
+        let is_dirty = self.index_of(id).map_or(false, |index| self.documents[index].is_dirty());
+
+        if is_dirty {
+            if let Some(index) = self.index_of(id) {
+                self.activate(index);
+            }
+
+            self.confirm = Some(pending);
+            Command::none()
+        } else {
+            self.proceed(pending)
+        }
+    }
+
+    fn find_next(&mut self) {
+        let index = self.focused();
+
+        if let Some(find) = self.find.as_ref() {
+            let content = &mut self.documents[index].content;
+            let text = content.text();
+            let from = cursor::cursor_offset(content, &text);
+
+            if let Some(range) = find.next(&text, from) {
+                cursor::select(content, &text, range);
+            }
+        }
+    }
+
+    fn save(&mut self, id: usize) -> Command<Message> {
+        let Some(document) = self.get_mut(id) else { return Command::none() };
+
+        // Whatever the route here, a truncated preview never goes back over
+        // the file it was cut from.
+        if document.saving.is_some() || document.partial {
+            return Command::none();
+        }
+
+        document.begin_save();
+
+        match (document.path.clone(), document.stamp) {
+            (Some(path), Some(_)) => Command::perform(file::stamp(path), move |stamp| Message::SaveChecked(id, stamp)),
+            _ => self.write(id)
+        }
+    }
+
+    fn write(&mut self, id: usize) -> Command<Message> {
+        let options = self.config.save.clone();
+        let directory = self.config.last_dir.clone();
+        let Some(document) = self.get_mut(id) else { return Command::none() };
+
+        if document.binary {
+            document.saving = None;
+            return Command::none();
+        }
+
+        document.prepare_save(&options);
+
+        document.begin_save();
+
+        let text = document.content.text();
+        Command::perform(
+            save_file(document.path.to_owned(), directory, text, document.format, document.line_ending, options),
+            move |result| Message::FileSave(id, result)
+        )
+    }
+
+    fn close(&mut self, id: usize) {
+        self.remember_position(id);
+
+        if self.split.map_or(false, |split| split.id == id) {
+            self.split = None;
+        }
+
+        if let Some(index) = self.index_of(id) {
+            self.documents.remove(index);
+
+            if index < self.active || self.active == self.documents.len() {
+                self.active = self.active.saturating_sub(1);
+            }
+        }
+
+        if self.documents.is_empty() {
+            self.add_document(None);
+        }
+    }
+
+    fn request_exit(&mut self) -> Command<Message> {
+        match self.documents.iter().find(|document| document.is_dirty()) {
+            Some(document) => {
+                let id = document.id;
+                self.guard_discard(PendingAction::Exit(id))
+            },
+            None => {
+                let ids = self.documents.iter().map(|document| document.id).collect::<Vec<_>>();
+
+                for id in ids {
+                    self.remember_position(id);
+                }
+
+                // Quit only once the positions are on disk.
+                self.quitting = true;
+                self.save_config()
+            }
+        }
+    }
+
+    fn remember_position(&mut self, id: usize) {
+        let Some(document) = self.get(id) else { return };
+        let Some(path) = document.path.clone() else { return };
+
+        if !document.is_placeholder() {
+            let position = document.content.cursor_position();
+            self.config.set_position(path, position);
+        }
+    }
+
+    fn set_find_options(&mut self, change: impl FnOnce(&mut find::Options)) -> Command<Message> {
+        change(&mut self.config.find);
+
+        if let Some(find) = self.find.as_mut() {
+            find.set_options(self.config.find);
+        }
+
+        self.save_config()
+    }
+
+    fn remember(&mut self, path: PathBuf) -> Command<Message> {
+        self.config.last_dir = path.parent().map(PathBuf::from);
+        self.config.recent.retain(|recent| *recent != path);
+        self.config.recent.insert(0, path);
+        self.config.recent.truncate(MAX_RECENT);
+
+        self.save_config()
+    }
+
+    fn convert_indentation(&mut self, tabs: bool) {
+        let width = self.config.tab_width;
+        let document = self.document_mut();
+
+        if document.read_only {
+            return;
+        }
+
+        let changed = document.reindent(width, tabs);
+
+        document.notice = Some(if changed == 0 {
+            String::from("Отступы не изменились")
+        } else {
+            format!("Изменено строк: {changed}")
+        });
+    }
+
+    fn highlighter_settings(&self, document: &Document) -> highlighter::Settings {
+        highlighter::Settings {
+            theme: match self.config.theme {
+                ThemeKind::Dark => highlighter::Theme::SolarizedDark,
+                ThemeKind::Light => highlighter::Theme::InspiredGitHub
+            },
+            extension: document.syntax().to_string()
+        }
+    }
+
+    /// One editor pane, with the gutter and guides when they are on.
+    fn editor_view<'a>(&'a self, document: &'a Document, pane: Pane) -> Element<'a, Message> {
+        let text = document.content.text();
+
+        let mut marks = highlight::Marks::new();
+
+        // Only the focused document is being searched.
+        if self.find.is_some() && document.id == self.document().id {
+            for range in self.find_matches().iter().take(MAX_MARKED_MATCHES) {
+                let (first, start) = cursor::position(&text, range.start);
+
+                // A regex match can run over several lines.
+                for (index, part) in text[range.clone()].split('\n').enumerate() {
+                    let column = if index == 0 { start } else { 0 };
+
+                    if !part.is_empty() {
+                        marks.entry(first + index).or_default().push((column..column + part.len(), highlight::Mark::Match));
+                    }
+                }
+            }
+        }
+
+        if let Some((bracket, matching)) = brackets::find_matching_bracket(&text, cursor::cursor_offset(&document.content, &text)) {
+            for offset in [bracket, matching] {
+                let (line, column) = cursor::position(&text, offset);
+                marks.entry(line).or_default().push((column..column + 1, highlight::Mark::Bracket));
+            }
+        }
+
+        let settings = highlight::Settings {
+            syntax: self.highlighter_settings(document),
+            marks
+        };
+
+        let on_edit = match pane {
+            Pane::Left => Message::Edit,
+            Pane::Right => Message::EditRight
+        };
+
+        let input = text_editor(&document.content)
+        .on_edit(on_edit)
+        .font(self.font_name.map(Font::with_name).unwrap_or_default())
+        .size(self.config.font_size)
+        .highlight::<highlight::Marked>(settings, |highlight, theme| {
+            highlight.to_format(&theme.palette())
+        });
+
+        if !self.scrolled_from_outside(document) {
+            return input.into();
+        }
+
+        // Both the gutter and the guides need the editor at its full size,
+        // scrolled from outside, to line up with its lines. It is as wide as
+        // the longest line so that no line wraps onto a second row.
+        let metrics = Metrics::new(self.config.font_size);
+        let longest = text
+            .split('\n')
+            .map(|line| lines::columns(line, self.config.tab_width))
+            .max()
+            .unwrap_or(0);
+
+        let input = container(input.height(Length::Shrink))
+            .width(2.0 * metrics.padding + (longest + 1) as f32 * metrics.advance);
+
+        let input: Element<_> = if self.shows_guides(document) {
+            let color = match self.config.theme {
+                ThemeKind::Dark => Color { a: 0.15, ..Color::WHITE },
+                ThemeKind::Light => Color { a: 0.15, ..Color::BLACK }
+            };
+            let levels = lines::indent_levels(&text, self.config.tab_width);
+
+            IndentGuides::new(input, levels, self.config.tab_width, metrics, color).into()
+        } else {
+            input.into()
+        };
+
+        let body: Element<_> = if self.config.show_line_numbers {
+            row![gutter(document.content.line_count(), self.config.font_size), input].into()
+        } else {
+            input
+        };
+
+        scrollable(Visible::new(body, move |visible| Message::Viewport(pane, visible)))
+            .id(pane_id(pane))
+            .direction(scrollable::Direction::Both {
+                vertical: scrollable::Properties::default(),
+                horizontal: scrollable::Properties::default()
+            })
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+
+    /// What the find bar matches in the active document, as of the last
+    /// update.
+    fn find_matches(&self) -> &[Range<usize>] {
+        self.matches.as_ref().map_or(&[], |(_, matches)| matches.as_slice())
+    }
+
+    fn refresh_matches(&mut self) {
+        let Some(find) = self.find.as_ref() else {
+            self.matches = None;
+            return;
+        };
+
+        let document = self.document();
+        let key = (document.id, document.revision, find.query.clone(), find.options);
+
+        if self.matches.as_ref().map_or(true, |(cached, _)| *cached != key) {
+            let matches = find.matches(&document.content.text());
+            self.matches = Some((key, matches));
+        }
+    }
+
+    fn shows_guides(&self, document: &Document) -> bool {
+        self.config.show_indent_guides && !matches!(document.syntax(), "txt" | "md")
+    }
+
+    /// Whether `document` is laid out at its full size inside a scrollable
+    /// rather than scrolling itself. That is also the only way to keep its
+    /// lines from wrapping, so the gutter and guides turn wrapping off.
+    fn scrolled_from_outside(&self, document: &Document) -> bool {
+        !self.config.word_wrap || self.config.show_line_numbers || self.shows_guides(document)
+    }
+
+    fn pane_document(&self, pane: Pane) -> Option<&Document> {
+        match pane {
+            Pane::Left => Some(&self.documents[self.active]),
+            Pane::Right => self.split.and_then(|split| self.get(split.id))
+        }
+    }
+
+    /// Which document each pane shows, and where its cursor is.
+    fn carets(&self) -> [Option<(usize, u64, (usize, usize))>; 2] {
+        [Pane::Left, Pane::Right].map(|pane| {
+            self.pane_document(pane)
+                .map(|document| (document.id, document.revision, document.content.cursor_position()))
+        })
+    }
+
+    /// Where the cursor of `document` is drawn in a pane scrolled from
+    /// outside, in the coordinates of what is scrolled.
+    fn cursor_bounds(&self, document: &Document) -> Rectangle {
+        let metrics = Metrics::new(self.config.font_size);
+        let text = document.content.text();
+        let (line, column) = document.content.cursor_position();
+        let before = &text[cursor::offset(&text, (line, 0))..cursor::offset(&text, (line, column))];
+
+        let gutter = if self.config.show_line_numbers {
+            gutter_width(document.content.line_count(), metrics)
+        } else {
+            0.0
+        };
+
+        Rectangle {
+            x: gutter + metrics.padding + lines::columns(before, self.config.tab_width) as f32 * metrics.advance,
+            y: metrics.padding + line as f32 * metrics.line_height,
+            width: metrics.advance,
+            height: metrics.line_height
+        }
+    }
+
+    /// Scrolls panes whose cursor moved since `before` so it stays in view.
+    /// The editor does this by itself unless it is scrolled from outside.
+    fn follow_cursors(&mut self, before: [Option<(usize, u64, (usize, usize))>; 2]) -> Command<Message> {
+        let after = self.carets();
+
+        let offsets = [Pane::Left, Pane::Right]
+            .into_iter()
+            .filter(|pane| after[*pane as usize].is_some() && after[*pane as usize] != before[*pane as usize])
+            .filter_map(|pane| {
+                let document = self.pane_document(pane).filter(|document| self.scrolled_from_outside(document))?;
+                let offset = scroll::follow(self.views[pane as usize]?, self.cursor_bounds(document))?;
+
+                Some((pane, offset))
+            })
+            .collect::<Vec<_>>();
+
+        Command::batch(offsets.into_iter().map(|(pane, offset)| self.scroll_to(pane, offset)))
+    }
+
+    /// Scrolls a pane scrolled from outside so its cursor is in the middle,
+    /// for jumps that can land anywhere.
+    fn center_cursor(&mut self, pane: Pane) -> Command<Message> {
+        let Some(document) = self.pane_document(pane).filter(|document| self.scrolled_from_outside(document)) else {
+            return Command::none()
+        };
+        let Some(visible) = self.views[pane as usize] else { return Command::none() };
+
+        let offset = scroll::center(visible, self.cursor_bounds(document));
+
+        self.scroll_to(pane, offset)
+    }
+
+    /// Scrolls the pane by whole lines, for the wheel over an editor that
+    /// has nothing to scroll itself.
+    fn scroll_lines(&mut self, pane: Pane, lines: i32) -> Command<Message> {
+        let Some(document) = self.pane_document(pane) else { return Command::none() };
+        let Some(visible) = self.views[pane as usize] else { return Command::none() };
+
+        let metrics = Metrics::new(self.config.font_size);
+        let height = 2.0 * metrics.padding + document.content.line_count() as f32 * metrics.line_height;
+        let y = (visible.y + lines as f32 * metrics.line_height).min(height - visible.height).max(0.0);
+
+        self.scroll_to(pane, scrollable::AbsoluteOffset { x: visible.x, y })
+    }
+
+    fn scroll_to(&mut self, pane: Pane, offset: scrollable::AbsoluteOffset) -> Command<Message> {
+        // Noted right away, in case another move comes before the pane
+        // reports where it ended up.
+        if let Some(visible) = self.views[pane as usize].as_mut() {
+            visible.x = offset.x;
+            visible.y = offset.y;
+        }
+
+        scrollable::scroll_to(pane_id(pane), offset)
+    }
+
+    /// What one level of indentation is typed as.
+    fn indent_unit(&self) -> String {
+        if self.config.insert_spaces {
+            " ".repeat(self.config.tab_width)
+        } else {
+            String::from("\t")
+        }
+    }
+
+    /// A tooltip with the key bound to `shortcut`, if there is one.
+    fn hint(&self, label: &str, shortcut: Shortcut) -> String {
+        match self.keymap.label(shortcut) {
+            Some(keys) => format!("{label} ({keys})"),
+            None => label.to_owned()
+        }
+    }
+
+    /// What the arrow key behind `motion` is bound to with the modifiers
+    /// held right now.
+    fn arrow_shortcut(&self, motion: text_editor::Motion) -> Option<Shortcut> {
+        let key_code = match motion {
+            text_editor::Motion::Up => keyboard::KeyCode::Up,
+            text_editor::Motion::Down => keyboard::KeyCode::Down,
+            _ => return None
+        };
+
+        self.keymap.get(key_code, self.modifiers)
+    }
+
+    /// Both editors see every key press, so only the pane clicked last acts
+    /// on them, through the same `edit` either way.
+    fn edit_in(&mut self, pane: Pane, action: text_editor::Action) -> Command<Message> {
+        let focused = match self.split.as_mut() {
+            Some(split) => {
+                if matches!(action, text_editor::Action::Click(_)) {
+                    split.focus = pane;
+                }
+
+                split.focus == pane
+            },
+            None => pane == Pane::Left
+        };
+
+        if focused {
+            self.edit(action)
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Applies an action from the editor in the focused pane, with the
+    /// indentation, bracket and shortcut handling on top of plain typing.
+    fn edit(&mut self, action: text_editor::Action) -> Command<Message> {
+        match action {
+            // Shortcuts like Ctrl+S can come through as characters too, and
+            // must not type anything. Ctrl+Alt is left alone, since that is
+            // how AltGr arrives on Windows.
+            text_editor::Action::Edit(text_editor::Edit::Insert(c))
+                if (c.is_control() && c != '\t') || (self.modifiers.command() && !self.modifiers.alt()) =>
+            {
+                Command::none()
+            },
+            // Tab over several lines, or Shift+Tab anywhere, shifts whole
+            // lines instead of typing over the selection.
+            text_editor::Action::Edit(text_editor::Edit::Insert('\t'))
+                if self.modifiers.shift() || self.document().spans_lines() =>
+            {
+                let width = self.config.tab_width;
+                let shift = self.modifiers.shift();
+                let unit = self.indent_unit();
+                let document = self.document_mut();
+
+                if shift {
+                    document.dedent(width);
+                } else {
+                    document.indent(&unit);
+                }
+
+                Command::none()
+            },
+            text_editor::Action::Edit(text_editor::Edit::Insert('\t')) if self.config.insert_spaces => {
+                let width = self.config.tab_width;
+                self.document_mut().insert_soft_tab(width);
+
+                Command::none()
+            },
+            text_editor::Action::Edit(text_editor::Edit::Backspace) if self.config.insert_spaces => {
+                let width = self.config.tab_width;
+                self.document_mut().backspace(width);
+
+                Command::none()
+            },
+            text_editor::Action::Edit(text_editor::Edit::Insert(c)) if self.config.auto_close_brackets => {
+                self.document_mut().insert_closing(c);
+
+                Command::none()
+            },
+            text_editor::Action::Edit(text_editor::Edit::Enter) if self.config.auto_indent => {
+                let unit = self.indent_unit();
+                self.document_mut().enter(&unit);
+
+                Command::none()
+            },
+            // The editor keeps arrow keys to itself even with modifiers held,
+            // so chords on them are picked out of its moves and looked up here.
+            text_editor::Action::Move(motion) | text_editor::Action::Select(motion)
+                if (self.modifiers.alt() || self.modifiers.command()) && self.arrow_shortcut(motion).is_some() =>
+            {
+                match self.arrow_shortcut(motion) {
+                    Some(shortcut) => self.update(shortcut_message(shortcut)),
+                    None => Command::none()
+                }
+            },
+            text_editor::Action::Move(text_editor::Motion::Home) => {
+                self.document_mut().smart_home(false);
+
+                Command::none()
+            },
+            text_editor::Action::Select(text_editor::Motion::Home) => {
+                self.document_mut().smart_home(true);
+
+                Command::none()
+            },
+            action => {
+                self.document_mut().edit(action);
+
+                Command::none()
+            }
+        }
+    }
+
+    fn save_config(&mut self) -> Command<Message> {
+        if self.config_saving {
+            self.config_queued = true;
+            return Command::none();
+        }
+
+        self.config_saving = true;
+        Command::perform(self.config.clone().save(), Message::ConfigSaved)
+    }
+
+    /// The state the window opens with: a tab for each file given, filled
+    /// once it loads, the piped text, or else one untitled buffer. Returns
+    /// the files still to be loaded.
+    fn start(Flags { files, stdin, config }: Flags) -> (Editor, Vec<PathBuf>) {
+        let font_name = config
+            .font_name
+            .clone()
+            .map(|name| &*Box::leak(name.into_boxed_str()));
+
+        let (keymap, invalid_keys) = Keymap::new(&config.keys);
+
+        let mut editor = Editor {
+            documents: Vec::new(),
+            active: 0,
+            next_id: 0,
+            find: None,
+            go_to: None,
+            sequence: None,
+            align: None,
+            confirm: None,
+            after_save: None,
+            conflict: None,
+            binary: None,
+            too_large: None,
+            loading: None,
+            split: None,
+            matches: None,
+            views: [None; 2],
+            config,
+            config_saving: false,
+            config_queued: false,
+            quitting: false,
+            font_name,
+            modifiers: keyboard::Modifiers::default(),
+            keymap
+        };
+
+        // Loading resolves paths, and the result is matched to its tab by
+        // path, so the tabs get resolved paths too.
+        let files = files.into_iter().map(file::absolute_now).collect::<Vec<_>>();
+
+        for path in &files {
+            editor.add_document(Some(path.clone()));
+        }
+
+        if let Some((text, line_ending)) = stdin {
+            editor.add_document(None);
+
+            if let Some(document) = editor.documents.last_mut() {
+                document.load_piped(text, line_ending);
+            }
+        }
+
+        if editor.documents.is_empty() {
+            editor.add_document(None);
+        }
+
+        editor.active = 0;
+
+        if !invalid_keys.is_empty() {
+            editor.document_mut().notice = Some(format!("Пропущены неверные сочетания клавиш: {}", invalid_keys.join(", ")));
+        }
+
+        (editor, files)
+    }
+
+    fn proceed(&mut self, pending: PendingAction) -> Command<Message> {
+        match pending {
+            PendingAction::Close(id) => {
+                self.close(id);
+                self.save_config()
+            },
+            PendingAction::Exit(id) => {
+                // Each dirty document is asked about in turn; once this one is
+                // dealt with, move on to the next or quit.
+                self.close(id);
+                self.request_exit()
+            },
+            PendingAction::Reload(id) => {
+                let path = self.index_of(id).and_then(|index| self.documents[index].path.clone());
+
+                match path {
+                    Some(path) => Command::perform(load_file(path), move |result| Message::FileReloaded(id, result)),
+                    None => Command::none()
+                }
+            },
+            PendingAction::Reopen(id, format) => {
+                let path = self.index_of(id).and_then(|index| self.documents[index].path.clone());
+
+                match path {
+                    Some(path) => Command::perform(reopen_file(path, format), move |result| Message::FileReloaded(id, result)),
+                    None => Command::none()
+                }
+            }
+        }
+    }
+}
+
+impl Application for Editor {
+    type Message = Message;
+    type Theme = Theme;
+    type Executor = executor::Default;
+    type Flags = Flags;
+
+    fn new(flags: Self::Flags) -> (Editor, Command<Message>) {
+        let (editor, files) = Editor::start(flags);
+        let limit = editor.config.large_file_limit;
+
+        // Each load reports back to its own placeholder, so a file that
+        // can't be read says so on its tab rather than the active one.
+        let loads = files
+            .into_iter()
+            .map(|path| {
+                let id = editor
+                    .documents
+                    .iter()
+                    .find(|document| document.path.as_ref() == Some(&path))
+                    .map_or(editor.document().id, |document| document.id);
+
+                Command::perform(open_file(path, limit), move |result| Message::FileOpened(id, result))
+            })
+            .collect::<Vec<_>>();
+
+        (editor, Command::batch(loads))
+    }
+
+    fn title(&self) -> String {
+        let document = self.document();
+        let name = document.name();
+
+        if document.is_dirty() {
+            format!("*{name}")
+        } else {
+            name
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> Command<Message> {
+        let carets = self.carets();
+
+        let command = match message {
+            // Laid out at its full size the editor has nothing to scroll, so
+            // the wheel over it scrolls the pane around it instead.
+            Message::Edit(text_editor::Action::Scroll { lines })
+                if self.pane_document(Pane::Left).map_or(false, |document| self.scrolled_from_outside(document)) =>
+            {
+                self.scroll_lines(Pane::Left, lines)
+            },
+            Message::EditRight(text_editor::Action::Scroll { lines })
+                if self.pane_document(Pane::Right).map_or(false, |document| self.scrolled_from_outside(document)) =>
+            {
+                self.scroll_lines(Pane::Right, lines)
+            },
+            Message::Viewport(pane, visible) => {
+                self.views[pane as usize] = Some(visible);
+
+                Command::none()
+            },
+            Message::KeyPressed(key_code, modifiers) => match self.keymap.get(key_code, modifiers) {
+                Some(shortcut) => self.update(shortcut_message(shortcut)),
+                None => Command::none()
+            },
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+
+                Command::none()
+            },
+            Message::Edit(action) => self.edit_in(Pane::Left, action),
+            Message::EditRight(action) => self.edit_in(Pane::Right, action),
+            Message::ExportHtml => {
+                let document = self.document();
+                let id = document.id;
+                let settings = self.highlighter_settings(document);
+                let title = document.name();
+                let directory = self.config.last_dir.clone();
+                let text = document.content.text();
+
+                // The page background of each highlighter theme.
+                let background = match self.config.theme {
+                    ThemeKind::Dark => Color::from_rgb8(0x00, 0x2b, 0x36),
+                    ThemeKind::Light => Color::WHITE
+                };
+
+                Command::perform(
+                    async move {
+                        let html = export::to_html(&text, &settings, &title, background);
+                        file::export_file(html.into_bytes(), directory, format!("{title}.html"), "html").await
+                    },
+                    move |result| Message::Exported(id, result)
+                )
+            },
+            Message::Print => {
+                let document = self.document();
+                let id = document.id;
+                let title = document.name();
+                let directory = self.config.last_dir.clone();
+                let text = document.content.text();
+                let (font_size, tab_width, line_numbers) = (self.config.font_size, self.config.tab_width, self.config.show_line_numbers);
+
+                Command::perform(
+                    async move {
+                        let pdf = export::to_pdf(&text, &title, font_size, tab_width, line_numbers)
+                            .map_err(|error| Error::ExportFailed(error.to_string()))?;
+
+                        file::export_file(pdf, directory, format!("{title}.pdf"), "pdf").await
+                    },
+                    move |result| Message::Exported(id, result)
+                )
+            },
+            Message::Exported(id, result) => match result {
+                Ok(path) => {
+                    if let Some(document) = self.get_mut(id) {
+                        document.notice = Some(format!("Экспортировано в {}", path.display()));
+                    }
+
+                    self.config.last_dir = path.parent().map(PathBuf::from);
+                    self.save_config()
+                },
+                Err(error) => {
+                    if let Some(document) = self.get_mut(id) {
+                        document.error = Some(error);
+                    }
+
+                    Command::none()
+                }
+            },
+            Message::ToggleSplit => {
+                self.split = match self.split {
+                    Some(_) => None,
+                    None => Some(Split { id: self.document().id, focus: Pane::Left })
+                };
+
+                Command::none()
+            },
+            Message::Undo => {
+                self.document_mut().undo();
+
+                Command::none()
+            },
+            Message::Redo => {
+                self.document_mut().redo();
+
+                Command::none()
+            },
+            Message::DuplicateLineOrSelection => {
+                self.document_mut().duplicate();
+
+                Command::none()
+            },
+            Message::TabsToSpaces => {
+                self.convert_indentation(false);
+
+                Command::none()
+            },
+            Message::SpacesToTabs => {
+                self.convert_indentation(true);
+
+                Command::none()
+            },
+            Message::SetIndentStyle(IndentStyle { spaces, width }) => {
+                self.config.insert_spaces = spaces;
+                self.config.tab_width = width;
+
+                self.save_config()
+            },
+            Message::ToggleComment => {
+                let document = self.document_mut();
+
+                if !document.read_only && !document.toggle_line_comment() {
+                    document.notice = Some(String::from("Для этого языка нет строчных комментариев"));
+                }
+
+                Command::none()
+            },
+            Message::ToggleBlockComment => {
+                let document = self.document_mut();
+
+                if !document.read_only {
+                    if let Err(note) = document.toggle_block_comment() {
+                        document.notice = Some(String::from(note));
+                    }
+                }
+
+                Command::none()
+            },
+            Message::MoveLines(direction) => {
+                self.document_mut().move_lines(direction);
+
+                Command::none()
+            },
+            Message::DeleteLine => {
+                self.document_mut().delete_lines();
+
+                Command::none()
+            },
+            Message::AddCursor => {
+                let document = self.document_mut();
+                let cursors = document.add_next_occurrence();
+
+                if cursors > 1 {
+                    document.notice = Some(format!("Курсоров: {cursors}"));
+                }
+
+                Command::none()
+            },
+            Message::JoinLines => {
+                self.document_mut().join_lines();
+
+                Command::none()
+            },
+            Message::InsertDate { utc } => {
+                let (stamp, warning) = match date::now(&self.config.date_format, utc) {
+                    Some(stamp) => (stamp, None),
+                    None => (
+                        date::now(date::DEFAULT_FORMAT, utc).unwrap_or_default(),
+                        Some(format!("Неверный формат даты «{}», использован стандартный", self.config.date_format))
+                    )
+                };
+
+                let document = self.document_mut();
+
+                if !document.read_only {
+                    document.edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(stamp))));
+                    document.notice = warning;
+                }
+
+                Command::none()
+            },
+            Message::Evaluate { append } => {
+                let document = self.document_mut();
+                document.notice = document.evaluate(append).err().map(String::from);
+
+                Command::none()
+            },
+            Message::JumpToMatchingBracket => {
+                self.document_mut().jump_to_matching_bracket();
+
+                Command::none()
+            },
+            Message::Reflow => {
+                let column = self.config.wrap_column;
+                let width = self.config.tab_width;
+                self.document_mut().reflow(column, width);
+
+                Command::none()
+            },
+            Message::TransposeChars => {
+                self.document_mut().rewrite_at_cursor(transpose::chars);
+
+                Command::none()
+            },
+            Message::TransposeWords => {
+                self.document_mut().rewrite_at_cursor(transpose::words);
+
+                Command::none()
+            },
+            Message::StepNumber(delta) => {
+                self.document_mut().rewrite_at_cursor(|text, offset| number::step(text, offset, delta));
+
+                Command::none()
+            },
+            Message::SortLines(sort) => {
+                self.document_mut().sort_lines(sort);
+
+                Command::none()
+            },
+            Message::ChangeCase(case) => {
+                self.document_mut().change_case(case);
+
+                Command::none()
+            },
+            Message::ReverseLines => {
+                self.document_mut().reverse_lines();
+
+                Command::none()
+            },
+            Message::UniqueLines { adjacent } => {
+                let ignore_trailing_whitespace = self.config.save.trim_on_save;
+                let document = self.document_mut();
+
+                if !document.read_only {
+                    let removed = document.unique_lines(adjacent, ignore_trailing_whitespace);
+                    document.notice = Some(format!("Удалено повторов: {removed}"));
+                }
+
+                Command::none()
+            },
+            Message::SelectAll => {
+                let content = &mut self.document_mut().content;
+
+                content.edit(text_editor::Action::Move(text_editor::Motion::DocumentStart));
+                content.edit(text_editor::Action::Select(text_editor::Motion::DocumentEnd));
+
+                Command::none()
+            },
+            Message::Copy => match self.document().content.selection() {
+                Some(selection) => clipboard::write(selection),
+                None => Command::none()
+            },
+            Message::Cut => {
+                let document = self.document_mut();
+
+                match document.content.selection() {
+                    Some(selection) if !document.read_only => {
+                        document.edit(text_editor::Action::Edit(text_editor::Edit::Delete));
+                        clipboard::write(selection)
+                    },
+                    _ => Command::none()
+                }
+            },
+            Message::Paste => clipboard::read(Message::Pasted),
+            Message::Pasted(contents) => {
+                if let Some(contents) = contents.filter(|contents| !contents.is_empty()) {
+                    self.document_mut().edit(text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(contents))));
+                }
+
+                Command::none()
+            },
+            Message::Open => {
+                let id = self.document().id;
+
+                Command::perform(
+                    pick_file(self.config.last_dir.clone(), self.config.large_file_limit),
+                    move |result| Message::FileOpened(id, result)
+                )
+            },
+            Message::FileOpened(_, Ok(loaded)) => {
+                let path = loaded.path.clone();
+                let existing = self
+                    .documents
+                    .iter()
+                    .position(|document| document.path.as_ref() == Some(&loaded.path));
+
+                if let Some(index) = existing {
+                    self.activate(index);
+
+                    if self.document().is_placeholder() {
+                        self.document_mut().load(loaded);
+                    }
+                } else {
+                    let position = self.config.position(&path);
+                    let document = if self.document().is_placeholder() && self.document().path.is_none() {
+                        self.document_mut()
+                    } else {
+                        self.add_document(None)
+                    };
+
+                    document.load(loaded);
+
+                    if let Some((line, column)) = position {
+                        document.restore_cursor(line, column);
+                    }
+                }
+
+                self.remember(path)
+            },
+            Message::FileDropped(path) => {
+                let id = self.document().id;
+
+                Command::perform(open_file(path, self.config.large_file_limit), move |result| Message::FileOpened(id, result))
+            },
+            Message::OpenRecent(path) => {
+                let id = self.document().id;
+
+                Command::perform(
+                    open_file(path.clone(), self.config.large_file_limit),
+                    move |result| Message::RecentOpened(id, path, result)
+                )
+            },
+            Message::RecentOpened(id, path, result) => {
+                let forget = if let Err(Error::IOFailed(_)) = result {
+                    self.config.recent.retain(|recent| *recent != path);
+                    self.save_config()
+                } else {
+                    Command::none()
+                };
+
+                Command::batch([forget, self.update(Message::FileOpened(id, result))])
+            },
+            Message::FileOpened(_, Err(Error::BinaryFile(path))) => {
+                self.binary = Some(path);
+                Command::none()
+            },
+            Message::OpenBinary(path) => {
+                self.binary = None;
+                let id = self.document().id;
+
+                Command::perform(load_binary(path), move |result| Message::FileOpened(id, result))
+            },
+            Message::FileOpened(_, Err(Error::TooLarge { path, size })) => {
+                self.too_large = Some((path, size));
+                Command::none()
+            },
+            Message::OpenLarge(path, size) => {
+                self.too_large = None;
+                self.loading = Some(Loading { id: self.next_id, from: self.document().id, path, size, progress: 0.0 });
+                self.next_id += 1;
+
+                Command::none()
+            },
+            Message::LoadProgress(progress) => {
+                if let Some(loading) = self.loading.as_mut() {
+                    loading.progress = progress;
+                }
+
+                Command::none()
+            },
+            Message::LoadFinished(result) => {
+                let Some(loading) = self.loading.take() else { return Command::none() };
+                self.update(Message::FileOpened(loading.from, result))
+            },
+            Message::CancelLoad => {
+                self.loading = None;
+                Command::none()
+            },
+            Message::PreviewLarge(path) => {
+                self.too_large = None;
+                let id = self.document().id;
+
+                Command::perform(preview_file(path, self.config.preview_lines), move |result| Message::FileOpened(id, result))
+            },
+            Message::CancelOpen => {
+                self.binary = None;
+                self.too_large = None;
+                Command::none()
+            },
+            Message::FileOpened(id, Err(error)) => {
+                match self.get_mut(id) {
+                    Some(document) => document.error = Some(error),
+                    None => self.document_mut().error = Some(error)
+                }
+
+                Command::none()
+            },
+            Message::FileReloaded(id, result) => {
+                if let Some(document) = self.get_mut(id) {
+                    match result {
+                        Ok(loaded) => document.load(loaded),
+                        Err(error) => document.error = Some(error)
+                    }
+                }
+
+                Command::none()
+            },
+            Message::New => {
+                self.add_document(None);
+                Command::none()
+            },
+            Message::SelectTab(index) => {
+                if index < self.documents.len() {
+                    self.activate(index);
+                }
+
+                Command::none()
+            },
+            Message::NextTab => {
+                self.activate((self.active + 1) % self.documents.len());
+                Command::none()
+            },
+            Message::PreviousTab => {
+                self.activate((self.active + self.documents.len() - 1) % self.documents.len());
+                Command::none()
+            },
+            Message::CloseTab(id) => self.guard_discard(PendingAction::Close(id)),
+            Message::CloseRequested => self.request_exit(),
+            Message::CloseActiveTab => {
+                let id = self.document().id;
+                self.guard_discard(PendingAction::Close(id))
+            },
+            Message::ConfirmDiscard(pending, Choice::Save) => {
+                self.confirm = None;
+                self.after_save = Some(pending);
+                self.save(pending.id())
+            },
+            Message::ConfirmDiscard(pending, Choice::Discard) => {
+                self.confirm = None;
+                self.proceed(pending)
+            },
+            Message::ConfirmDiscard(_, Choice::Cancel) => {
+                self.confirm = None;
+                Command::none()
+            },
+            Message::FileSave(id, Ok(saved)) => {
+                let path = saved.path.clone();
+
+                if let Some(document) = self.get_mut(id) {
+                    document.saved(saved);
+                }
+
+                self.remember_position(id);
+                let remember = self.remember(path);
+
+                let next = match self.after_save.take() {
+                    Some(pending) => self.proceed(pending),
+                    None => Command::none()
+                };
+
+                Command::batch([remember, next])
+            },
+            Message::FileSave(id, Err(error)) => {
+                self.after_save = None;
+
+                if let Some(document) = self.get_mut(id) {
+                    document.saving = None;
+                    document.error = Some(error);
+                }
+
+                Command::none()
+            },
+            // A truncated preview would replace the whole file it came from,
+            // and the dialog offers that very file first.
+            Message::Save | Message::SaveAs if self.document().binary || self.document().partial => Command::none(),
+            Message::Save if self.document().read_only => self.update(Message::SaveAs),
+            Message::Save => self.save(self.document().id),
+            Message::SaveChecked(id, stamp) => {
+                let Some(index) = self.index_of(id) else { return Command::none() };
+
+                if stamp == self.documents[index].stamp {
+                    self.write(id)
+                } else {
+                    self.documents[index].saving = None;
+                    self.activate(index);
+                    self.conflict = Some(id);
+                    Command::none()
+                }
+            },
+            Message::FileChangedOnDisk(path) => {
+                let document = self.documents.iter().find(|document| document.path.as_ref() == Some(&path));
+
+                match document {
+                    Some(document) => {
+                        let id = document.id;
+                        Command::perform(file::stamp(path), move |stamp| Message::DiskChecked(id, stamp))
+                    },
+                    None => Command::none()
+                }
+            },
+            Message::DiskChecked(id, stamp) => {
+                if let Some(document) = self.get_mut(id) {
+                    // Our own saves touch the file too; they update the stamp
+                    // once they land, so only a stamp we never saw counts.
+                    if document.saving.is_none() && document.stamp.is_some() && stamp != document.stamp {
+                        document.changed_on_disk = true;
+                    }
+                }
+
+                Command::none()
+            },
+            Message::ReloadChanged => {
+                let document = self.document_mut();
+                document.changed_on_disk = false;
+
+                let id = document.id;
+
+                match document.path.clone() {
+                    Some(path) => Command::perform(load_file(path), move |result| Message::FileReloaded(id, result)),
+                    None => Command::none()
+                }
+            },
+            Message::KeepChanged => {
+                self.document_mut().changed_on_disk = false;
+                Command::none()
+            },
+            Message::ResolveConflict(resolution) => {
+                let Some(id) = self.conflict.take() else { return Command::none() };
+                let path = self.index_of(id).and_then(|index| self.documents[index].path.clone());
+
+                match (resolution, path) {
+                    (Resolution::Overwrite, _) => self.write(id),
+                    (Resolution::Reload, Some(path)) => {
+                        self.after_save = None;
+                        Command::perform(load_file(path), move |result| Message::FileReloaded(id, result))
+                    },
+                    _ => {
+                        self.after_save = None;
+                        Command::none()
+                    }
+                }
+            },
+            Message::Reload => {
+                let id = self.document().id;
+
+                if self.document().path.is_some() {
+                    self.guard_discard(PendingAction::Reload(id))
+                } else {
+                    Command::none()
+                }
+            },
+            Message::SaveWithEncoding(format) => {
+                let document = self.document_mut();
+                document.set_format(format);
+
+                let id = document.id;
+                self.save(id)
+            },
+            Message::SyntaxSelected(name) => {
+                let extension = SYNTAXES
+                    .iter()
+                    .find(|(label, _)| *label == name)
+                    .map(|(_, extension)| extension.to_string());
+
+                self.document_mut().syntax_override = extension;
+
+                Command::none()
+            },
+            Message::SetBom(bom) => {
+                self.document_mut().set_bom(bom);
+
+                Command::none()
+            },
+            Message::NormalizeLineEndings => {
+                self.document_mut().normalize_line_endings();
+
+                Command::none()
+            },
+            Message::SetLineEnding(line_ending) => {
+                self.document_mut().set_line_ending(line_ending);
+
+                Command::none()
+            },
+            Message::ReopenWithEncoding(format) => {
+                let id = self.document().id;
+
+                if self.document().path.is_some() {
+                    self.guard_discard(PendingAction::Reopen(id, format))
+                } else {
+                    Command::none()
+                }
+            },
+            Message::SaveAs => {
+                let options = self.config.save.clone();
+                let directory = self.config.last_dir.clone();
+                let document = self.document_mut();
+
+                if document.saving.is_some() {
+                    return Command::none();
+                }
+
+                document.prepare_save(&options);
+
+                document.begin_save();
+
+                let id = document.id;
+                let text = document.content.text();
+
+                Command::perform(
+                    save_file_as(document.path.to_owned(), directory, text, document.format, document.line_ending, options),
+                    move |result| Message::FileSave(id, result)
+                )
+            },
+            Message::ToggleBackup(backup) => {
+                self.config.save.backup = backup;
+
+                self.save_config()
+            },
+            Message::ToggleTrimOnSave(trim_on_save) => {
+                self.config.save.trim_on_save = trim_on_save;
+
+                self.save_config()
+            },
+            Message::SetFinalNewline(final_newline) => {
+                self.config.save.final_newline = final_newline;
+
+                self.save_config()
+            },
+            Message::ToggleAutoClose(auto_close_brackets) => {
+                self.config.auto_close_brackets = auto_close_brackets;
+
+                self.save_config()
+            },
+            Message::ToggleAutoIndent(auto_indent) => {
+                self.config.auto_indent = auto_indent;
+
+                self.save_config()
+            },
+            Message::ToggleAutoSave(auto_save) => {
+                self.config.auto_save = auto_save;
+
+                self.save_config()
+            },
+            Message::AutoSaveTick => {
+                // Untitled buffers would need a dialog, so they are left alone.
+                let ids = self
+                    .documents
+                    .iter()
+                    .filter(|document| document.is_dirty() && document.path.is_some())
+                    .filter(|document| !document.read_only && !document.binary)
+                    .map(|document| document.id)
+                    .collect::<Vec<_>>();
+
+                Command::batch(ids.into_iter().map(|id| self.save(id)).collect::<Vec<_>>())
+            },
+            Message::ConfigSaved(result) => {
+                self.config_saving = false;
+
+                if let Err(error) = result {
+                    self.document_mut().notice = Some(format!("Не удалось сохранить настройки: {error}"));
+                }
+
+                if self.config_queued {
+                    self.config_queued = false;
+                    self.save_config()
+                } else if self.quitting {
+                    window::close()
+                } else {
+                    Command::none()
+                }
+            },
+            Message::ToggleReadOnly => {
+                let document = self.document_mut();
+                document.read_only = !document.read_only || document.binary || document.partial;
+
+                Command::none()
+            },
+            Message::ToggleLineNumbers => {
+                self.config.show_line_numbers = !self.config.show_line_numbers;
+
+                self.save_config()
+            },
+            Message::ToggleIndentGuides => {
+                self.config.show_indent_guides = !self.config.show_indent_guides;
+
+                self.save_config()
+            },
+            Message::ToggleWordWrap => {
+                self.config.word_wrap = !self.config.word_wrap;
+
+                self.save_config()
+            },
+            Message::ToggleTheme => {
+                self.config.theme = match self.config.theme {
+                    ThemeKind::Dark => ThemeKind::Light,
+                    ThemeKind::Light => ThemeKind::Dark
+                };
+
+                self.save_config()
+            },
+            Message::IncreaseFontSize => {
+                self.config.font_size = (self.config.font_size + 1.0).min(MAX_FONT_SIZE);
+                self.save_config()
+            },
+            Message::DecreaseFontSize => {
+                self.config.font_size = (self.config.font_size - 1.0).max(MIN_FONT_SIZE);
+                self.save_config()
+            },
+            Message::ResetFontSize => {
+                self.config.font_size = DEFAULT_FONT_SIZE;
+                self.save_config()
+            },
+            Message::ToggleGoTo => {
+                self.go_to = match self.go_to.take() {
+                    Some(_) => None,
+                    None => Some(GoTo::default())
+                };
+
+                Command::none()
+            },
+            Message::GoToChanged(input) => {
+                self.go_to = Some(GoTo { input, invalid: false });
+                Command::none()
+            },
+            Message::GoToSubmitted => {
+                let Some(go_to) = self.go_to.as_mut() else { return Command::none() };
+
+                match go_to.parse() {
+                    Some((line, column)) => {
+                        self.go_to = None;
+                        self.update(Message::GoToLine(line, column))
+                    },
+                    None => {
+                        go_to.invalid = true;
+                        Command::none()
+                    }
+                }
+            },
+            Message::GoToLine(line, column) => {
+                let document = self.document_mut();
+                let line = line.clamp(1, document.content.line_count().max(1));
+                let text = document.content.text();
+
+                // The column is clamped to the end of the line. Moving the
+                // cursor scrolls an editor that scrolls itself.
+                let offset = cursor::offset(&text, (line - 1, column.map_or(0, |column| column - 1)));
+                cursor::move_to(&mut document.content, &text, offset);
+
+                self.center_cursor(Pane::Left)
+            },
+            Message::ToggleSequence => {
+                self.sequence = match self.sequence.take() {
+                    Some(_) => None,
+                    None => Some(Sequence::default())
+                };
+
+                Command::none()
+            },
+            Message::SequenceStartChanged(start) => {
+                if let Some(sequence) = self.sequence.as_mut() {
+                    sequence.start = start;
+                }
+
+                Command::none()
+            },
+            Message::SequenceStepChanged(step) => {
+                if let Some(sequence) = self.sequence.as_mut() {
+                    sequence.step = step;
+                }
+
+                Command::none()
+            },
+            Message::SequenceWidthChanged(width) => {
+                if let Some(sequence) = self.sequence.as_mut() {
+                    sequence.width = width;
+                }
+
+                Command::none()
+            },
+            Message::InsertSequence => {
+                let Some(numbers) = self.sequence.as_ref().and_then(Sequence::parse) else { return Command::none() };
+
+                self.document_mut().insert_sequence(numbers);
+                self.sequence = None;
+
+                Command::none()
+            },
+            Message::ToggleAlign => {
+                self.align = match self.align.take() {
+                    Some(_) => None,
+                    None => Some(String::from("="))
+                };
+
+                Command::none()
+            },
+            Message::AlignChanged(separator) => {
+                self.align = Some(separator);
+                Command::none()
+            },
+            Message::AlignLines => {
+                let Some(separator) = self.align.take() else { return Command::none() };
+                let width = self.config.tab_width;
+                let document = self.document_mut();
+
+                if !document.read_only {
+                    let moved = document.align(&separator, width);
+                    document.notice = Some(if moved == 0 {
+                        String::from("Строки уже выровнены")
+                    } else {
+                        format!("Выровнено строк: {moved}")
+                    });
+                }
+
+                Command::none()
+            },
+            Message::ToggleFind => {
+                self.find = match self.find.take() {
+                    Some(_) => None,
+                    None => Some(Find::new(self.config.find))
+                };
+
+                Command::none()
+            },
+            Message::Dismiss => {
+                let document = self.document_mut();
 
-    fn title(&self) -> String {
-        String::from("Dota 2")
-    }
+                if !document.cursors.is_empty() {
+                    document.cursors.clear();
+                    document.notice = None;
+                } else if self.go_to.is_some() {
+                    self.go_to = None;
+                } else if self.sequence.is_some() {
+                    self.sequence = None;
+                } else if self.align.is_some() {
+                    self.align = None;
+                } else {
+                    self.find = None;
+                }
 
-    fn update(&mut self, message: Self::Message) -> Command<Message> {
-        match message {
-            Message::Edit(action) => {
-                self.is_dirty = self.is_dirty || action.is_edit();
-                self.content.edit(action);
+                Command::none()
+            },
+            Message::FindChanged(query) => {
+                if let Some(find) = self.find.as_mut() {
+                    find.set_query(query);
+                }
 
                 Command::none()
             },
-            Message::Open => Command::perform(pick_file(), Message::FileOpened),
-            Message::FileOpened(Ok((path, content))) => {
-                self.path = Some(path);
-                self.content = text_editor::Content::with(content.as_str());
-                self.error = None;
+            Message::ToggleInSelection(in_selection) => {
+                if let Some(find) = self.find.as_mut() {
+                    find.in_selection = in_selection;
+                }
 
                 Command::none()
             },
-            Message::FileOpened(Err(error)) => {
-                self.is_dirty = false;
-                self.error = Some(error);
+            Message::ToggleRegex(regex) => self.set_find_options(|options| options.regex = regex),
+            Message::ToggleMatchCase(match_case) => self.set_find_options(|options| options.match_case = match_case),
+            Message::ToggleWholeWord(whole_word) => self.set_find_options(|options| options.whole_word = whole_word),
+            Message::ReplaceChanged(replacement) => {
+                if let Some(find) = self.find.as_mut() {
+                    find.replacement = replacement;
+                }
+
                 Command::none()
             },
-            Message::New => {
-                self.is_dirty = true;
-                self.path = None;
-                self.content = text_editor::Content::with("");
-                self.error = None;
-            
+            Message::FindNext => {
+                self.find_next();
+
                 Command::none()
             },
-            Message::FileSave(Ok(path)) => {
-                self.path = Some(path);
-                self.is_dirty = false;
+            Message::FindPrevious => {
+                let index = self.focused();
+
+                if let Some(find) = self.find.as_ref() {
+                    let content = &mut self.documents[index].content;
+                    let text = content.text();
+                    let selected = content.selection().map_or(0, |selection| selection.len());
+                    let anchor = cursor::cursor_offset(content, &text).saturating_sub(selected);
+
+                    if let Some(range) = find.previous(&text, anchor) {
+                        cursor::select(content, &text, range);
+                    }
+                }
+
                 Command::none()
             },
-            Message::FileSave(Err(error)) => {
-                self.error = Some(error);
+            Message::Replace => {
+                let index = self.focused();
+
+                if let Some(find) = self.find.as_ref().filter(|find| !find.query.is_empty()) {
+                    let document = &mut self.documents[index];
+
+                    if let Some(selection) = document.content.selection().filter(|selection| find.is_match(selection)) {
+                        let replacement = Arc::new(find.replacement_for(&selection));
+                        document.edit(text_editor::Action::Edit(text_editor::Edit::Paste(replacement)));
+                    }
+
+                    self.find_next();
+                }
+
                 Command::none()
             },
-            Message::Save => {
-                let text = self.content.text();
-                Command::perform( save_file(self.path.to_owned(), text), Message::FileSave)
+            Message::ReplaceAll => {
+                let Some(find) = self.find.clone().filter(|find| !find.query.is_empty()) else { return Command::none() };
+
+                let document = self.document_mut();
+
+                if document.read_only {
+                    return Command::none();
+                }
+
+                let (id, revision) = (document.id, document.revision);
+                let text = document.content.text();
+                let selection = cursor::selection_range(&document.content, &text);
+
+                if find.in_selection && selection.is_none() {
+                    document.notice = Some(String::from("Нет выделения"));
+                    return Command::none();
+                }
+
+                let range = selection.filter(|_| find.in_selection);
+
+                // The string work happens off the update loop so huge files
+                // don't freeze the window; the result is applied as one swap.
+                Command::perform(
+                    async move {
+                        match range {
+                            Some(range) => {
+                                let (text, count, range) = find.replace_in(&text, range);
+                                (text, count, Some(range))
+                            },
+                            None => {
+                                let (text, count) = find.replace_all(&text);
+                                (text, count, None)
+                            }
+                        }
+                    },
+                    move |(text, count, range)| Message::Replaced(id, revision, text, count, range)
+                )
+            },
+            Message::Replaced(id, revision, text, count, range) => {
+                if let Some(document) = self.get_mut(id) {
+                    // Typing during the replace would be lost by the swap, and a
+                    // document made read-only meanwhile takes no replacements.
+                    if document.revision == revision && !document.read_only {
+                        if count > 0 {
+                            document.set_text(text);
+                        }
+
+                        // Keep the selection so the replace can be run again.
+                        if let Some(range) = range {
+                            let text = document.content.text();
+                            cursor::select(&mut document.content, &text, range);
+                        }
+
+                        document.notice = Some(format!("Заменено: {count}"));
+                    }
+                }
+
+                Command::none()
             }
-        }
+        };
+
+        self.refresh_matches();
+
+        Command::batch([command, self.follow_cursors(carets)])
     }
 
     fn view(&self) -> Element<'_, Message> {
+        if let Some(pending @ (PendingAction::Reload(_) | PendingAction::Reopen(..))) = self.confirm {
+            return prompt("Отменить все изменения и загрузить файл с диска?", vec![
+                choice("Вернуть", theme::Button::Destructive, Message::ConfirmDiscard(pending, Choice::Discard)),
+                choice("Отмена", theme::Button::Secondary, Message::ConfirmDiscard(pending, Choice::Cancel))
+            ]);
+        }
+
+        if let Some(pending) = self.confirm {
+            return prompt("В файле есть несохранённые изменения. Сохранить их?", vec![
+                choice("Сохранить", theme::Button::Primary, Message::ConfirmDiscard(pending, Choice::Save)),
+                choice("Не сохранять", theme::Button::Destructive, Message::ConfirmDiscard(pending, Choice::Discard)),
+                choice("Отмена", theme::Button::Secondary, Message::ConfirmDiscard(pending, Choice::Cancel))
+            ]);
+        }
+
+        if self.conflict.is_some() {
+            return prompt("Файл был изменён другой программой после открытия.", vec![
+                choice("Перезаписать", theme::Button::Destructive, Message::ResolveConflict(Resolution::Overwrite)),
+                choice("Загрузить с диска", theme::Button::Primary, Message::ResolveConflict(Resolution::Reload)),
+                choice("Отмена", theme::Button::Secondary, Message::ResolveConflict(Resolution::Cancel))
+            ]);
+        }
+
+        if let Some(path) = self.binary.as_ref() {
+            return prompt(&format!("{} похож на двоичный файл. Открыть его только для просмотра?", path.display()), vec![
+                choice("Открыть только для чтения", theme::Button::Destructive, Message::OpenBinary(path.clone())),
+                choice("Отмена", theme::Button::Secondary, Message::CancelOpen)
+            ]);
+        }
+
+        if let Some((path, size)) = self.too_large.as_ref() {
+            return prompt(&format!("{} занимает {}. Открыть его целиком?", path.display(), file::format_size(*size)), vec![
+                choice("Открыть всё равно", theme::Button::Destructive, Message::OpenLarge(path.clone(), *size)),
+                choice(
+                    &format!("Первые {} строк", self.config.preview_lines),
+                    theme::Button::Primary,
+                    Message::PreviewLarge(path.clone())
+                ),
+                choice("Отмена", theme::Button::Secondary, Message::CancelOpen)
+            ]);
+        }
+
+        let document = self.document();
 
         let controls_bar = {
-            let open_file = action(folder_icon(), "Открыть файл",  Some(Message::Open));
-            let new_file = action(new_icon(), "Новый файл", Some(Message::New));
-            let save_file =  action(save_icon(), "Сохранить файл",  self.is_dirty.then_some(Message::Save));
-
-            row![new_file, open_file, save_file].spacing(10)
-        };
-
-        let input = text_editor(&self.content)
-        .on_edit(Message::Edit)
-        .highlight::<Highlighter>(highlighter::Settings {
-            theme: highlighter::Theme::SolarizedDark,
-            extension: self
-            .path
-            .as_ref()
-            .and_then(|path| path.extension()?.to_str())
-            .unwrap_or("rs")
-            .to_string()
-        }, |highlight, _theme| {
-            highlight.to_format()
+            let open_file = action(folder_icon(), &self.hint("Открыть файл", Shortcut::Open), Some(Message::Open));
+            let new_file = action(new_icon(), &self.hint("Новый файл", Shortcut::New), Some(Message::New));
+            let save_file = if document.saving.is_some() {
+                action(saving_icon(), "Сохранение…", None)
+            } else {
+                action(save_icon(), &self.hint("Сохранить файл", Shortcut::Save), (document.is_dirty() && !document.read_only).then_some(Message::Save))
+            };
+            let save_file_as = action(save_as_icon(), &self.hint("Сохранить как", Shortcut::SaveAs), (!document.binary && !document.partial).then_some(Message::SaveAs));
+            let revert = action(
+                revert_icon(),
+                "Вернуть сохранённую версию",
+                (document.is_dirty() && document.path.is_some()).then_some(Message::Reload)
+            );
+
+            let undo = action(undo_icon(), &self.hint("Отменить", Shortcut::Undo), (document.history.can_undo() && !document.read_only).then_some(Message::Undo));
+            let redo = action(redo_icon(), &self.hint("Повторить", Shortcut::Redo), (document.history.can_redo() && !document.read_only).then_some(Message::Redo));
+
+            let read_only = if document.read_only {
+                action(read_only_icon(true), "Разрешить правку", (!document.binary && !document.partial).then_some(Message::ToggleReadOnly))
+            } else {
+                action(read_only_icon(false), "Только чтение", Some(Message::ToggleReadOnly))
+            };
+            let line_numbers = action(line_numbers_icon(), "Номера строк", Some(Message::ToggleLineNumbers));
+            let indent_guides = action(indent_guides_icon(), "Направляющие отступов", Some(Message::ToggleIndentGuides));
+            let split = action(split_icon(), "Разделить окно", Some(Message::ToggleSplit));
+            let export_html = action(export_icon(), "Экспорт в HTML", Some(Message::ExportHtml));
+            let print = action(print_icon(), "Печать в PDF", Some(Message::Print));
+            let theme = action(theme_icon(), "Светлая/тёмная тема", Some(Message::ToggleTheme));
+
+            let syntax = pick_list(
+                SYNTAXES.iter().map(|(label, _)| label.to_string()).collect::<Vec<_>>(),
+                SYNTAXES
+                    .iter()
+                    .find(|(_, extension)| *extension == document.syntax())
+                    .map(|(label, _)| label.to_string()),
+                Message::SyntaxSelected
+            )
+            .placeholder("Синтаксис");
+
+            let edit_menu = pick_list(EditCommand::ALL, None, EditCommand::message).placeholder("Правка");
+
+
+            let recent = pick_list(
+                self.config.recent.iter().cloned().map(RecentFile).collect::<Vec<_>>(),
+                None,
+                |RecentFile(path)| Message::OpenRecent(path)
+            )
+            .placeholder("Недавние файлы");
+
+            let backup = checkbox("Резервная копия", self.config.save.backup, Message::ToggleBackup);
+            let trim_on_save = checkbox("Убирать пробелы в конце строк", self.config.save.trim_on_save, Message::ToggleTrimOnSave);
+            let final_newline = pick_list(FinalNewline::ALL, Some(self.config.save.final_newline), Message::SetFinalNewline);
+            let auto_save = checkbox("Автосохранение", self.config.auto_save, Message::ToggleAutoSave);
+            let auto_indent = checkbox("Автоотступ", self.config.auto_indent, Message::ToggleAutoIndent);
+            let auto_close = checkbox("Закрывать скобки", self.config.auto_close_brackets, Message::ToggleAutoClose);
+            let word_wrap = checkbox("Перенос строк", self.config.word_wrap, Message::ToggleWordWrap);
+
+            row![new_file, open_file, recent, save_file, save_file_as, export_html, print, revert, undo, redo, edit_menu, read_only, line_numbers, indent_guides, split, theme, horizontal_space(Length::Fill), syntax, word_wrap, auto_indent, auto_close, trim_on_save, final_newline, auto_save, backup]
+                .spacing(10)
+                .align_items(Alignment::Center)
+        };
+
+        let tabs = {
+            let tabs = self.documents.iter().enumerate().map(|(index, document)| {
+                let name = if document.is_dirty() {
+                    format!("*{}", document.name())
+                } else {
+                    document.name()
+                };
+
+                Element::from(row![
+                    button(text(name))
+                        .on_press(Message::SelectTab(index))
+                        .style(if index == self.active {
+                            theme::Button::Primary
+                        } else {
+                            theme::Button::Secondary
+                        }),
+                    button(text("×"))
+                        .on_press(Message::CloseTab(document.id))
+                        .style(theme::Button::Text)
+                ])
+            });
+
+            Row::with_children(tabs.collect())
+                .push(button(text("+")).on_press(Message::New).style(theme::Button::Text))
+                .spacing(5)
+        };
+
+        let go_to_bar = self.go_to.as_ref().map(|go_to| {
+            let hint = if go_to.invalid {
+                text("Введите строку или строка:столбец").style(Color::from_rgb(0.9, 0.3, 0.3))
+            } else {
+                text("")
+            };
+
+            row![
+                text("Перейти к"),
+                text_input("строка:столбец", &go_to.input)
+                    .on_input(Message::GoToChanged)
+                    .on_submit(Message::GoToSubmitted)
+                    .width(150),
+                hint
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
+        let sequence_bar = self.sequence.as_ref().map(|sequence| {
+            let preview = match sequence.parse() {
+                Some(numbers) => text(format!("{}, …", numbers.take(3).collect::<Vec<_>>().join(", "))),
+                None => text("Нужны целые числа").style(Color::from_rgb(0.9, 0.3, 0.3))
+            };
+
+            row![
+                text("Последовательность с"),
+                text_input("1", &sequence.start)
+                    .on_input(Message::SequenceStartChanged)
+                    .on_submit(Message::InsertSequence)
+                    .width(80),
+                text("шаг"),
+                text_input("1", &sequence.step)
+                    .on_input(Message::SequenceStepChanged)
+                    .on_submit(Message::InsertSequence)
+                    .width(80),
+                text("цифр"),
+                text_input("без дополнения", &sequence.width)
+                    .on_input(Message::SequenceWidthChanged)
+                    .on_submit(Message::InsertSequence)
+                    .width(120),
+                preview,
+                button(text("Вставить")).on_press(Message::InsertSequence)
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
+        let align_bar = self.align.as_ref().map(|separator| {
+            row![
+                text("Выровнять по"),
+                text_input("=", separator)
+                    .on_input(Message::AlignChanged)
+                    .on_submit(Message::AlignLines)
+                    .width(80),
+                button(text("Выровнять")).on_press_maybe((!separator.is_empty()).then_some(Message::AlignLines))
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
+        let find_bar = self.find.as_ref().map(|find| {
+            let contents = document.content.text();
+            let matches = self.find_matches();
+
+            // A match the cursor was put on by Next/Previous is selected,
+            // with the cursor sitting at its end.
+            let cursor = cursor::cursor_offset(&document.content, &contents);
+            let current = document
+                .content
+                .selection()
+                .and_then(|_| matches.iter().position(|range| range.end == cursor));
+
+            let counter = match (current, matches.len()) {
+                // regex errors draw an ASCII pointer above the message, the
+                // last line alone reads fine in a single-line bar.
+                _ if find.error.is_some() => format!(
+                    "неверное выражение: {}",
+                    find.error.as_deref().and_then(|error| error.lines().last()).unwrap_or_default()
+                ),
+                (_, 0) if find.query.is_empty() => String::new(),
+                (Some(index), total) => format!("{} из {}", index + 1, total),
+                (None, total) => format!("совпадений: {total}")
+            };
+
+            row![
+                text_input("Найти", &find.query)
+                    .on_input(Message::FindChanged)
+                    .on_submit(Message::FindNext),
+                text_input("Заменить", &find.replacement)
+                    .on_input(Message::ReplaceChanged)
+                    .on_submit(Message::Replace),
+                button(text("Назад")).on_press(Message::FindPrevious),
+                button(text("Далее")).on_press(Message::FindNext),
+                button(text("Заменить")).on_press_maybe((!document.read_only).then_some(Message::Replace)),
+                button(text("Заменить все")).on_press_maybe((!document.read_only).then_some(Message::ReplaceAll)),
+                checkbox("Aa", find.options.match_case, Message::ToggleMatchCase),
+                checkbox("Слово", find.options.whole_word, Message::ToggleWholeWord),
+                checkbox(".*", find.options.regex, Message::ToggleRegex),
+                if document.content.selection().is_some() {
+                    Element::from(checkbox("в выделении", find.in_selection, Message::ToggleInSelection))
+                } else {
+                    text("в выделении").style(Color::from_rgb(0.5, 0.5, 0.5)).into()
+                },
+                if find.error.is_some() || (!find.query.is_empty() && matches.is_empty()) {
+                    text(counter).style(Color::from_rgb(0.9, 0.3, 0.3))
+                } else {
+                    text(counter)
+                }
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
         });
 
+        let input = self.editor_view(&self.documents[self.active], Pane::Left);
+
+        let input = match self.split.and_then(|split| self.documents.iter().find(|document| document.id == split.id)) {
+            Some(other) => row![input, self.editor_view(other, Pane::Right)].spacing(10).into(),
+            None => input
+        };
+
         let status_bar = {
-            let status = if let Some(Error::IOFailed(error)) = self.error.as_ref() {
-                text(error.to_string())
+            let status = if let Some(error) = document.error.as_ref().and_then(Error::describe) {
+                text(error)
+            } else if let Some(notice) = document.notice.as_ref() {
+                text(notice)
             } else {
-                match self.path.as_deref().and_then(Path::to_str) {
-                    Some(path) => text(path).size(18),
+                match document.path.as_deref() {
+                    Some(path) => text(path.display()).size(18),
                     None => text("Новый файл")
                 }
             };
 
-            let position: Text = {
-                let (line, column) = self.content.cursor_position();
-                text(format!("{}:{}", line + 1, column + 1))
+            let position = {
+                let (line, column) = document.content.cursor_position();
+                let label: Text = text(format!("{}:{}", line + 1, column + 1)).size(self.config.font_size);
+
+                button(label).on_press(Message::ToggleGoTo).style(theme::Button::Text)
             };
 
-            row![status, horizontal_space(Length::Fill), position]
+            let counts = text(format!("слов: {}, символов: {}", document.counts.words, document.counts.chars));
+
+            let mut status_bar = row![status, horizontal_space(Length::Fill)].spacing(10);
+
+            if document.read_only {
+                status_bar = status_bar.push(text("только чтение"));
+            }
+
+            if document.partial {
+                status_bar = status_bar.push(text(format!("первые {} строк", document.content.line_count())));
+            }
+
+            if document.lossy {
+                status_bar = status_bar.push(text("декодировано с потерями"));
+            }
+
+            if document.mixed_endings {
+                status_bar = status_bar.push(
+                    button(text("смешанные окончания строк"))
+                        .on_press(Message::NormalizeLineEndings)
+                        .style(theme::Button::Text)
+                );
+            }
+
+            if document.format.supports_bom() {
+                status_bar = status_bar.push(checkbox("BOM", document.format.bom, Message::SetBom));
+            }
+
+            let line_ending = pick_list(LineEnding::ALL, Some(document.line_ending), Message::SetLineEnding);
+
+            let encoding = pick_list(Format::all(), Some(document.format), Message::SaveWithEncoding);
+            let reopen = pick_list(Format::all(), None, Message::ReopenWithEncoding)
+                .placeholder("Открыть в кодировке…");
+
+            let indent_style = pick_list(
+                IndentStyle::all(),
+                Some(IndentStyle { spaces: self.config.insert_spaces, width: self.config.tab_width }),
+                Message::SetIndentStyle
+            );
+
+            status_bar = status_bar.push(indent_style).push(line_ending).push(reopen).push(encoding);
+
+            if let Some(stamp) = document.stamp {
+                status_bar = status_bar.push(text(file::format_size(stamp.size())));
+            }
+
+            status_bar.push(counts).push(position)
         };    
 
-        container(column![controls_bar, input, status_bar].spacing(10))
+        let mut layout = column![controls_bar, tabs].spacing(10);
+
+        if let Some(go_to_bar) = go_to_bar {
+            layout = layout.push(go_to_bar);
+        }
+
+        if let Some(sequence_bar) = sequence_bar {
+            layout = layout.push(sequence_bar);
+        }
+
+        if let Some(align_bar) = align_bar {
+            layout = layout.push(align_bar);
+        }
+
+        if let Some(find_bar) = find_bar {
+            layout = layout.push(find_bar);
+        }
+
+        if document.changed_on_disk {
+            let warning = if document.is_dirty() {
+                "Файл изменён другой программой. Загрузка с диска отменит ваши несохранённые изменения."
+            } else {
+                "Файл изменён другой программой."
+            };
+
+            layout = layout.push(
+                row![
+                    text(warning),
+                    horizontal_space(Length::Fill),
+                    button(text("Загрузить с диска")).on_press(Message::ReloadChanged).style(if document.is_dirty() {
+                        theme::Button::Destructive
+                    } else {
+                        theme::Button::Primary
+                    }),
+                    button(text("Оставить")).on_press(Message::KeepChanged).style(theme::Button::Secondary)
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+            );
+        }
+
+        if let Some(loading) = self.loading.as_ref() {
+            layout = layout.push(
+                row![
+                    text(format!(
+                        "Загрузка {}: {} из {}",
+                        loading.path.display(),
+                        file::format_size((loading.size as f64 * loading.progress as f64) as u64),
+                        file::format_size(loading.size)
+                    )),
+                    progress_bar(0.0..=1.0, loading.progress),
+                    button(text("Отмена")).on_press(Message::CancelLoad).style(theme::Button::Secondary)
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+            );
+        }
+
+        container(layout.push(input).push(status_bar))
             .padding(10)
             .into()
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        match self.config.theme {
+            ThemeKind::Dark => Theme::Dark,
+            ThemeKind::Light => Theme::Light
+        }
     }
 
     fn subscription(&self) -> iced::Subscription<Self::Message> {
-        keyboard::on_key_press(|key_code, modofiers| match key_code  {
-            keyboard::KeyCode::S if modofiers.command() => Some(Message::Save),
+        // Looked up in `update`, since the keymap comes from the settings.
+        let keys = keyboard::on_key_press(|key_code, modifiers| Some(Message::KeyPressed(key_code, modifiers)));
+
+        // Every dropped file arrives as its own event and opens in its own tab.
+        let window_events = subscription::events_with(|event, _status| match event {
+            event::Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+            event::Event::Window(window::Event::CloseRequested) => Some(Message::CloseRequested),
+            event::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => Some(Message::ModifiersChanged(modifiers)),
             _ => None
-        })
+        });
+
+        let mut subscriptions = vec![keys, window_events];
+
+        if self.config.auto_save {
+            let interval = Duration::from_secs(self.config.auto_save_interval.max(1));
+            subscriptions.push(time::every(interval).map(|_| Message::AutoSaveTick));
+        }
+
+        // Every open file is watched, so a tab in the background still
+        // learns that its file changed before it is saved over.
+        for path in self.documents.iter().filter_map(|document| document.path.clone()) {
+            subscriptions.push(watch::changes(path).map(Message::FileChangedOnDisk));
+        }
+
+        if let Some(loading) = self.loading.as_ref() {
+            subscriptions.push(file::load_with_progress(loading.id, loading.path.clone()).map(|progress| match progress {
+                file::Progress::Advanced(progress) => Message::LoadProgress(progress),
+                file::Progress::Finished(result) => Message::LoadFinished(result)
+            }));
+        }
+
+        iced::Subscription::batch(subscriptions)
     }
 
 }
@@ -178,6 +2650,57 @@ fn action<'a>(icon: Element<'a, Message>, label: &str, action: Option<Message> )
     .into()
 }
 
+fn prompt<'a>(question: &str, choices: Vec<Element<'a, Message>>) -> Element<'a, Message> {
+    let choices = Row::with_children(choices).spacing(10);
+
+    container(column![text(question), choices].spacing(20))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x()
+        .center_y()
+        .into()
+}
+
+fn choice<'a>(label: &str, style: theme::Button, message: Message) -> Element<'a, Message> {
+    button(text(label))
+        .style(style)
+        .on_press(message)
+        .into()
+}
+
+fn pane_id(pane: Pane) -> scrollable::Id {
+    scrollable::Id::new(match pane {
+        Pane::Left => "left",
+        Pane::Right => "right"
+    })
+}
+
+fn gutter_width(line_count: usize, metrics: Metrics) -> f32 {
+    const MIN_DIGITS: usize = 4;
+
+    let digits = line_count.max(1).to_string().len().max(MIN_DIGITS);
+
+    digits as f32 * metrics.advance + 10.0
+}
+
+fn gutter<'a>(line_count: usize, font_size: f32) -> Element<'a, Message> {
+    let numbers = (1..=line_count.max(1))
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    container(
+        text(numbers)
+            .font(Font::MONOSPACE)
+            .size(font_size)
+            .horizontal_alignment(alignment::Horizontal::Right)
+            .width(Length::Fill)
+    )
+    .width(gutter_width(line_count, Metrics::new(font_size)))
+    .padding([5, 10, 5, 0])
+    .into()
+}
+
 fn new_icon<'a>() -> Element<'a, Message> {
     icon('\u{E800}')
 }
@@ -190,58 +2713,317 @@ fn save_icon<'a>() -> Element<'a, Message> {
     icon('\u{E801}')
 }
 
-fn icon<'a, Message>(codepoint: char) -> Element<'a, Message> {
-    const ICON_FONT: Font = Font::with_name("editor-icons");
+fn saving_icon<'a>() -> Element<'a, Message> {
+    text("⏳").into()
+}
 
-    text(codepoint).font(ICON_FONT).into()
+fn save_as_icon<'a>() -> Element<'a, Message> {
+    text("…").into()
 }
 
-async fn save_file(path: Option<PathBuf>, text: String) -> Result<PathBuf, Error> {
-    let path = if let Some(path) = path { path } else {
-        rfd::AsyncFileDialog::new()
-        .set_title("Choose a file name...")
-        .save_file()
-        .await
-        .ok_or(Error::DialogClosed)
-        .map(|handle| handle.path().to_owned())?
-    };
+fn undo_icon<'a>() -> Element<'a, Message> {
+    text("↶").into()
+}
+
+fn redo_icon<'a>() -> Element<'a, Message> {
+    text("↷").into()
+}
 
-    tokio::fs::write(&path, text)
-    .await
-    .map_err(|err| Error::IOFailed(err.kind()))?;
+fn read_only_icon<'a>(locked: bool) -> Element<'a, Message> {
+    text(if locked { "RO" } else { "RW" }).into()
+}
 
-    Ok(path)
+fn line_numbers_icon<'a>() -> Element<'a, Message> {
+    text("#").into()
 }
 
-fn default_file() -> PathBuf {
-    PathBuf::from(format!("{}/src/main.rs", env!("CARGO_MANIFEST_DIR")))
+fn indent_guides_icon<'a>() -> Element<'a, Message> {
+    text("⋮").into()
 }
 
-async fn load_file(path: PathBuf) -> Result<(PathBuf, Arc<String>), Error> {
-    let content = tokio::fs::read_to_string(&path)
-    .await
-    .map(Arc::new)
-    .map_err(|error| Error::IOFailed(error.kind()))?;
+fn export_icon<'a>() -> Element<'a, Message> {
+    text("</>").into()
+}
 
-    Ok((path, content))
+fn print_icon<'a>() -> Element<'a, Message> {
+    text("PDF").into()
 }
 
+fn split_icon<'a>() -> Element<'a, Message> {
+    text("⫼").into()
+}
 
+fn theme_icon<'a>() -> Element<'a, Message> {
+    text("◐").into()
+}
 
-async fn pick_file() -> Result<(PathBuf, Arc<String>), Error> {
-    let handle = rfd::AsyncFileDialog::new()
-        .set_title("Выберите файл")
-        .pick_file()
-        .await.ok_or(Error::DialogClosed)?;
+fn revert_icon<'a>() -> Element<'a, Message> {
+    text("↺").into()
+}
 
-    let path = handle.path(); 
+fn icon<'a, Message>(codepoint: char) -> Element<'a, Message> {
+    const ICON_FONT: Font = Font::with_name("editor-icons");
 
-    load_file(path.to_owned()).await
+    text(codepoint).font(ICON_FONT).into()
 }
 
 pub fn main() -> iced::Result {
+    let (piped, files): (Vec<_>, Vec<_>) = env::args_os().skip(1).partition(|arg| arg.as_os_str() == "-");
+    let files = files.into_iter().map(PathBuf::from).collect();
+
+    let stdin = if !piped.is_empty() && !io::stdin().is_terminal() {
+        file::read_stdin()
+    } else {
+        None
+    };
+
+    let config = EditorConfig::load();
+
     Editor::run(Settings {
         fonts: vec![include_bytes!("../fonts/editor-icons.ttf").as_slice().into()],
-        ..Settings::default()
+        exit_on_close_request: false,
+        ..Settings::with_flags(Flags { files, stdin, config })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(files: Vec<PathBuf>, stdin: Option<(String, LineEnding)>) -> (Editor, Vec<PathBuf>) {
+        Editor::start(Flags { files, stdin, config: EditorConfig::default() })
+    }
+
+    #[test]
+    fn starting_without_files_opens_a_clean_untitled_buffer() {
+        let (editor, loads) = start(Vec::new(), None);
+
+        assert!(loads.is_empty());
+        assert_eq!(editor.documents.len(), 1);
+
+        let document = editor.document();
+
+        assert!(document.path.is_none());
+        assert!(document.error.is_none());
+        assert!(!document.is_dirty());
+        assert_eq!(document.name(), "Untitled");
+        assert_eq!(editor.title(), "Untitled");
+    }
+
+    #[test]
+    fn starting_with_files_loads_each_into_its_own_tab() {
+        let files = vec![PathBuf::from("a.txt"), PathBuf::from("b.rs")];
+        let (editor, loads) = start(files.clone(), None);
+
+        assert_eq!(loads, files);
+        assert_eq!(editor.active, 0);
+        assert_eq!(
+            editor.documents.iter().map(|document| document.path.clone()).collect::<Vec<_>>(),
+            files.into_iter().map(Some).collect::<Vec<_>>()
+        );
+        assert!(editor.documents.iter().all(|document| !document.is_dirty() && document.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn a_relative_path_from_the_command_line_loads_into_its_own_tab() {
+        let (mut editor, loads) = start(vec![PathBuf::from("Cargo.toml")], None);
+        assert!(loads[0].is_absolute());
+
+        let loaded = open_file(PathBuf::from("Cargo.toml"), u64::MAX).await;
+        let id = editor.document().id;
+        let _ = editor.update(Message::FileOpened(id, loaded));
+
+        assert_eq!(editor.documents.len(), 1);
+        assert_eq!(editor.document().path.as_ref(), Some(&loads[0]));
+        assert!(editor.document().content.text().contains("[package]"));
+    }
+
+    #[tokio::test]
+    async fn a_file_that_fails_to_open_reports_on_its_own_tab() {
+        let files = vec![PathBuf::from("/nonexistent/a.txt"), PathBuf::from("/nonexistent/b.txt")];
+        let (mut editor, _) = start(files.clone(), None);
+        let id = editor.documents[0].id;
+        let _ = editor.update(Message::SelectTab(1));
+
+        let failed = open_file(files[0].clone(), u64::MAX).await;
+        let _ = editor.update(Message::FileOpened(id, failed));
+
+        assert!(editor.documents[0].error.is_some());
+        assert!(editor.documents[1].error.is_none());
+        assert_eq!(editor.active, 1);
+    }
+
+    #[test]
+    fn a_truncated_preview_cannot_be_saved_at_all() {
+        let (mut editor, _) = start(Vec::new(), None);
+        let document = editor.document_mut();
+        document.path = Some(PathBuf::from("/tmp/large.log"));
+        document.partial = true;
+        document.read_only = true;
+
+        let _ = editor.update(Message::Save);
+        let _ = editor.update(Message::SaveAs);
+        let _ = editor.update(Message::SaveWithEncoding(Format::default()));
+
+        assert!(editor.document().saving.is_none());
+    }
+
+    #[test]
+    fn the_right_pane_types_and_undoes_like_the_left() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.config.auto_close_brackets = true;
+
+        let _ = editor.update(Message::New);
+        let _ = editor.update(Message::ToggleSplit);
+        let _ = editor.update(Message::SelectTab(0));
+
+        let left = editor.documents[editor.active].id;
+        let right = editor.split.unwrap().id;
+        assert_ne!(left, right);
+
+        let _ = editor.update(Message::EditRight(text_editor::Action::Click(iced::Point::ORIGIN)));
+        let _ = editor.update(Message::EditRight(text_editor::Action::Edit(text_editor::Edit::Insert('('))));
+        // The left editor sees the same key press and must leave it alone.
+        let _ = editor.update(Message::Edit(text_editor::Action::Edit(text_editor::Edit::Insert('('))));
+
+        assert_eq!(editor.get(right).unwrap().content.text(), "()");
+        assert_eq!(editor.get(left).unwrap().content.text(), "");
+
+        let _ = editor.update(Message::Undo);
+        assert_eq!(editor.get(right).unwrap().content.text(), "");
+
+        // Picking a tab puts the focus back on the left.
+        let _ = editor.update(Message::SelectTab(0));
+        assert_eq!(editor.document().id, left);
+    }
+
+    #[test]
+    fn replacing_in_a_read_only_document_reports_nothing() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.document_mut().set_text(String::from("aaa"));
+        editor.document_mut().read_only = true;
+
+        let _ = editor.update(Message::ToggleFind);
+        let _ = editor.update(Message::FindChanged(String::from("a")));
+        let _ = editor.update(Message::ReplaceAll);
+
+        let (id, revision) = (editor.document().id, editor.document().revision);
+        let _ = editor.update(Message::Replaced(id, revision, String::from("bbb"), 3, None));
+
+        assert_eq!(editor.document().content.text(), "aaa");
+        assert!(editor.document().notice.is_none());
+    }
+
+    #[test]
+    fn settings_are_saved_one_at_a_time_with_the_latest_last() {
+        let (mut editor, _) = start(Vec::new(), None);
+
+        let _ = editor.update(Message::IncreaseFontSize);
+        let _ = editor.update(Message::IncreaseFontSize);
+        assert!(editor.config_saving && editor.config_queued);
+
+        // The queued save starts once the first one lands.
+        let _ = editor.update(Message::ConfigSaved(Ok(())));
+        assert!(editor.config_saving && !editor.config_queued);
+
+        let _ = editor.update(Message::ConfigSaved(Ok(())));
+        assert!(!editor.config_saving);
+    }
+
+    #[test]
+    fn a_failed_settings_save_is_reported() {
+        let (mut editor, _) = start(Vec::new(), None);
+
+        let _ = editor.update(Message::ConfigSaved(Err(std::io::ErrorKind::PermissionDenied)));
+
+        assert!(!editor.config_saving);
+        assert!(editor.document().notice.as_ref().is_some_and(|notice| notice.starts_with("Не удалось сохранить настройки")));
+    }
+
+    #[test]
+    fn moving_the_cursor_out_of_view_scrolls_the_gutter_pane_to_it() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.config.show_line_numbers = true;
+        editor.document_mut().set_text(vec!["line"; 100].join("\n"));
+
+        let visible = Rectangle { x: 0.0, y: 0.0, width: 800.0, height: 200.0 };
+        let _ = editor.update(Message::Viewport(Pane::Left, visible));
+        let _ = editor.update(Message::Edit(text_editor::Action::Move(text_editor::Motion::DocumentEnd)));
+
+        let visible = editor.views[Pane::Left as usize].unwrap();
+        let cursor = editor.cursor_bounds(editor.document());
+
+        assert!(visible.y > 0.0);
+        assert!(cursor.y >= visible.y && cursor.y + cursor.height <= visible.y + visible.height);
+    }
+
+    #[test]
+    fn lines_wrap_only_with_wrapping_on_and_the_gutter_and_guides_off() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.config.show_line_numbers = false;
+        editor.config.show_indent_guides = false;
+
+        assert!(!editor.scrolled_from_outside(editor.document()));
+
+        let _ = editor.update(Message::ToggleWordWrap);
+        assert!(!editor.config.word_wrap);
+        assert!(editor.scrolled_from_outside(editor.document()));
+
+        let _ = editor.update(Message::ToggleWordWrap);
+        editor.config.show_line_numbers = true;
+        assert!(editor.scrolled_from_outside(editor.document()));
+    }
+
+    #[test]
+    fn going_to_a_line_centers_it_in_the_gutter_pane() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.config.show_line_numbers = true;
+        editor.document_mut().set_text(vec!["line"; 100].join("\n"));
+
+        let visible = Rectangle { x: 0.0, y: 0.0, width: 800.0, height: 200.0 };
+        let _ = editor.update(Message::Viewport(Pane::Left, visible));
+        let _ = editor.update(Message::GoToLine(60, None));
+
+        assert_eq!(editor.document().content.cursor_position(), (59, 0));
+
+        let visible = editor.views[Pane::Left as usize].unwrap();
+        let cursor = editor.cursor_bounds(editor.document());
+
+        assert!((cursor.center_y() - visible.center_y()).abs() < 1.0);
+
+        // Line 1 is where a fresh buffer starts, but not where this cursor is.
+        let _ = editor.update(Message::GoToLine(1, Some(1)));
+        assert_eq!(editor.document().content.cursor_position(), (0, 0));
+        assert_eq!(editor.views[Pane::Left as usize].unwrap().y, 0.0);
+    }
+
+    #[test]
+    fn find_matches_are_found_again_when_the_text_or_query_changes() {
+        let (mut editor, _) = start(Vec::new(), None);
+        editor.document_mut().set_text(String::from("aba"));
+
+        let _ = editor.update(Message::ToggleFind);
+        let _ = editor.update(Message::FindChanged(String::from("a")));
+        assert_eq!(editor.find_matches(), &[0..1, 2..3]);
+
+        editor.document_mut().set_text(String::from("abaa"));
+        let _ = editor.update(Message::FindChanged(String::from("a")));
+        assert_eq!(editor.find_matches(), &[0..1, 2..3, 3..4]);
+
+        let _ = editor.update(Message::FindChanged(String::from("b")));
+        assert_eq!(editor.find_matches(), &[1..2]);
+
+        let _ = editor.update(Message::ToggleFind);
+        assert!(editor.find_matches().is_empty());
+    }
+
+    #[test]
+    fn piped_text_starts_as_an_unsaved_buffer() {
+        let (editor, loads) = start(Vec::new(), Some((String::from("piped"), LineEnding::Lf)));
+
+        assert!(loads.is_empty());
+        assert_eq!(editor.documents.len(), 1);
+        assert!(editor.document().path.is_none());
+        assert!(editor.document().is_dirty());
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Range;
+
+/// Adds `delta` to the number the cursor is on or just after, keeping its
+/// width when it has leading zeros. Decimals may carry a minus sign and
+/// `0x` numbers keep the case of their digits. Returns the new text and
+/// where the cursor goes, still on the number.
+pub fn step(text: &str, offset: usize, delta: i64) -> Option<(String, usize)> {
+    // A `0x` number that can't take the step is left alone rather than
+    // having its last digit stepped as a decimal.
+    let (range, replacement) = match hex_literal(text, offset) {
+        Some(range) => hex(text, range, delta)?,
+        None => decimal(text, offset, delta)?
+    };
+
+    let cursor = range.start + (offset - range.start).min(replacement.len());
+    let text = format!("{}{}{}", &text[..range.start], replacement, &text[range.end..]);
+
+    Some((text, cursor))
+}
+
+/// The run of bytes matching `matches` that `offset` is in or touches.
+fn run_around(text: &str, offset: usize, matches: impl Fn(u8) -> bool) -> Range<usize> {
+    let bytes = text.as_bytes();
+
+    let start = bytes[..offset].iter().rposition(|&byte| !matches(byte)).map_or(0, |index| index + 1);
+    let end = bytes[offset..].iter().position(|&byte| !matches(byte)).map_or(bytes.len(), |index| offset + index);
+
+    start..end
+}
+
+/// The `0x` number `offset` is in or touches, prefix included.
+fn hex_literal(text: &str, offset: usize) -> Option<Range<usize>> {
+    let range = run_around(text, offset, |byte| byte.is_ascii_alphanumeric());
+    let digits = text[range.clone()].strip_prefix("0x").or_else(|| text[range.clone()].strip_prefix("0X"))?;
+
+    (!digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_hexdigit())).then_some(range)
+}
+
+fn hex(text: &str, range: Range<usize>, delta: i64) -> Option<(Range<usize>, String)> {
+    let digits = &text[range.start + 2..range.end];
+
+    let value = u128::from_str_radix(digits, 16).ok()?;
+    let value = value.checked_add_signed(delta.into())?;
+
+    let width = digits.len();
+    let formatted = if digits.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        format!("{value:0width$X}")
+    } else {
+        format!("{value:0width$x}")
+    };
+
+    let prefix = &text[range.start..range.start + 2];
+
+    Some((range, format!("{prefix}{formatted}")))
+}
+
+fn decimal(text: &str, offset: usize, delta: i64) -> Option<(Range<usize>, String)> {
+    let digits = run_around(text, offset, |byte| byte.is_ascii_digit());
+
+    if digits.is_empty() {
+        return None;
+    }
+
+    // A minus right after a word is subtraction, not a sign.
+    let before = &text[..digits.start];
+    let negative = before
+        .strip_suffix('-')
+        .map_or(false, |rest| !rest.chars().next_back().map_or(false, |c| c.is_alphanumeric() || c == '_'));
+
+    let start = if negative { digits.start - 1 } else { digits.start };
+    let value = text[start..digits.end].parse::<i128>().ok()?.checked_add(delta.into())?;
+
+    let original = &text[digits.clone()];
+    let width = if original.len() > 1 && original.starts_with('0') { original.len() } else { 1 };
+
+    let formatted = if value < 0 {
+        format!("-{:0width$}", value.unsigned_abs())
+    } else {
+        format!("{value:0width$}")
+    };
+
+    Some((start..digits.end, formatted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_hex_keeping_width_and_case() {
+        assert_eq!(step("x = 0x0F;", 6, 1), Some((String::from("x = 0x10;"), 6)));
+        assert_eq!(step("0xff", 2, 1), Some((String::from("0x100"), 2)));
+    }
+
+    #[test]
+    fn leaves_hex_alone_when_it_would_go_negative() {
+        assert_eq!(step("0x0", 2, -1), None);
+        assert_eq!(step("0x0", 0, -1), None);
+    }
+
+    #[test]
+    fn steps_decimals_through_zero() {
+        assert_eq!(step("a = 0", 4, -1), Some((String::from("a = -1"), 4)));
+        assert_eq!(step("007", 0, 1), Some((String::from("008"), 0)));
+    }
+}
@@ -0,0 +1,225 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{self, tree, Tree, Widget};
+use iced::advanced::{Clipboard, Shell};
+use iced::widget::scrollable::AbsoluteOffset;
+use iced::{event, mouse, Element, Event, Length, Rectangle};
+
+/// Where text sits in a `text_editor` at a given font size. It doesn't
+/// expose its metrics, so these match its defaults, with the usual advance
+/// of a monospace font.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub padding: f32,
+    pub line_height: f32,
+    pub advance: f32
+}
+
+impl Metrics {
+    pub fn new(font_size: f32) -> Self {
+        Metrics {
+            padding: 5.0,
+            line_height: font_size * 1.3,
+            advance: font_size * 0.6
+        }
+    }
+}
+
+/// Where to scroll so `target` is inside `visible`, or `None` when it
+/// already is. Both are in the coordinates of the scrolled content.
+pub fn follow(visible: Rectangle, target: Rectangle) -> Option<AbsoluteOffset> {
+    let x = reveal(visible.x, visible.width, target.x, target.width);
+    let y = reveal(visible.y, visible.height, target.y, target.height);
+
+    (x != visible.x || y != visible.y).then_some(AbsoluteOffset { x, y })
+}
+
+/// Like `follow`, but puts `target` in the middle of the view vertically,
+/// for jumps that can land far from what was on screen.
+pub fn center(visible: Rectangle, target: Rectangle) -> AbsoluteOffset {
+    AbsoluteOffset {
+        x: reveal(visible.x, visible.width, target.x, target.width),
+        y: (target.y + target.height / 2.0 - visible.height / 2.0).max(0.0)
+    }
+}
+
+/// The least the view starting at `start` has to move along one axis to
+/// show the span at `target`. A span longer than the view shows its start.
+fn reveal(start: f32, length: f32, target: f32, size: f32) -> f32 {
+    if target < start || size > length {
+        target
+    } else if target + size > start + length {
+        target + size - length
+    } else {
+        start
+    }
+}
+
+/// Reports which part of its content a surrounding scrollable shows, in
+/// the content's own coordinates, whenever that changes. The scrollable
+/// only says so when the user drags it, and only after the first time.
+pub struct Visible<'a, Message, Renderer> {
+    content: Element<'a, Message, Renderer>,
+    on_change: Box<dyn Fn(Rectangle) -> Message + 'a>
+}
+
+impl<'a, Message, Renderer> Visible<'a, Message, Renderer> {
+    pub fn new(content: impl Into<Element<'a, Message, Renderer>>, on_change: impl Fn(Rectangle) -> Message + 'a) -> Self {
+        Visible {
+            content: content.into(),
+            on_change: Box::new(on_change)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    reported: Option<Rectangle>
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Visible<'a, Message, Renderer>
+where
+    Renderer: renderer::Renderer
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, tree: &mut Tree, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.as_widget().layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle
+    ) {
+        self.content.as_widget().draw(&tree.children[0], renderer, theme, style, layout, cursor, viewport);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        let visible = Rectangle {
+            x: viewport.x - bounds.x,
+            y: viewport.y - bounds.y,
+            ..*viewport
+        };
+
+        // Published ahead of the event itself, so a key press that moves the
+        // cursor is handled knowing what was on screen.
+        let state = tree.state.downcast_mut::<State>();
+
+        if state.reported != Some(visible) {
+            state.reported = Some(visible);
+            shell.publish((self.on_change)(visible));
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(&tree.children[0], layout, cursor, viewport, renderer)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation<Message>
+    ) {
+        self.content.as_widget().operate(&mut tree.children[0], layout, renderer, operation);
+    }
+}
+
+impl<'a, Message, Renderer> From<Visible<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Message: 'a,
+    Renderer: renderer::Renderer + 'a
+{
+    fn from(visible: Visible<'a, Message, Renderer>) -> Self {
+        Element::new(visible)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle { x, y, width, height }
+    }
+
+    #[test]
+    fn a_target_in_view_needs_no_scrolling() {
+        assert_eq!(follow(rectangle(0.0, 100.0, 300.0, 200.0), rectangle(10.0, 150.0, 10.0, 20.0)), None);
+    }
+
+    #[test]
+    fn scrolls_just_far_enough_to_show_the_target() {
+        let visible = rectangle(0.0, 100.0, 300.0, 200.0);
+
+        assert_eq!(follow(visible, rectangle(10.0, 290.0, 10.0, 20.0)), Some(AbsoluteOffset { x: 0.0, y: 110.0 }));
+        assert_eq!(follow(visible, rectangle(10.0, 40.0, 10.0, 20.0)), Some(AbsoluteOffset { x: 0.0, y: 40.0 }));
+        assert_eq!(follow(visible, rectangle(400.0, 150.0, 10.0, 20.0)), Some(AbsoluteOffset { x: 110.0, y: 100.0 }));
+    }
+
+    #[test]
+    fn centering_stops_at_the_top() {
+        let visible = rectangle(0.0, 500.0, 300.0, 200.0);
+
+        assert_eq!(center(visible, rectangle(10.0, 1000.0, 10.0, 20.0)), AbsoluteOffset { x: 0.0, y: 910.0 });
+        assert_eq!(center(visible, rectangle(10.0, 20.0, 10.0, 20.0)), AbsoluteOffset { x: 0.0, y: 0.0 });
+    }
+}
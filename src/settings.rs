@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::date;
+use crate::file::{self, Error, SaveOptions};
+use crate::find;
+use crate::history;
+use crate::line_ending::LineEnding;
+
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+pub const MIN_FONT_SIZE: f32 = 8.0;
+pub const MAX_FONT_SIZE: f32 = 48.0;
+pub const MAX_RECENT: usize = 10;
+pub const MAX_POSITIONS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemeKind {
+    Dark,
+    Light
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    pub theme: ThemeKind,
+    pub font_size: f32,
+    pub font_name: Option<String>,
+    pub show_line_numbers: bool,
+    pub show_indent_guides: bool,
+    // Lines only wrap with line numbers and indent guides off, since both
+    // need every line on one row.
+    pub word_wrap: bool,
+    // Where Reflow breaks lines.
+    pub wrap_column: usize,
+    // `strftime` format for Insert Date/Time.
+    pub date_format: String,
+    pub line_ending: LineEnding,
+    pub auto_save: bool,
+    pub auto_save_interval: u64,
+    pub large_file_limit: u64,
+    pub preview_lines: usize,
+    pub recent: Vec<PathBuf>,
+    // Where the last file dialog ended up, to start the next one there.
+    pub last_dir: Option<PathBuf>,
+    // Where the cursor was in each file when it was last closed or saved,
+    // most recent first.
+    pub positions: Vec<(PathBuf, (usize, usize))>,
+    pub history_depth: usize,
+    pub find: find::Options,
+    pub tab_width: usize,
+    pub insert_spaces: bool,
+    pub auto_indent: bool,
+    pub auto_close_brackets: bool,
+    pub save: SaveOptions,
+    // Key chords like `Ctrl+Enter` to command names, on top of the default
+    // bindings. An empty name unbinds the chord.
+    pub keys: BTreeMap<String, String>
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        EditorConfig {
+            theme: ThemeKind::Dark,
+            font_size: DEFAULT_FONT_SIZE,
+            font_name: None,
+            show_line_numbers: true,
+            show_indent_guides: true,
+            word_wrap: true,
+            wrap_column: 80,
+            date_format: String::from(date::DEFAULT_FORMAT),
+            line_ending: LineEnding::default(),
+            auto_save: false,
+            auto_save_interval: 30,
+            large_file_limit: 10 * 1024 * 1024,
+            preview_lines: 1000,
+            recent: Vec::new(),
+            last_dir: None,
+            positions: Vec::new(),
+            history_depth: history::DEFAULT_LIMIT,
+            find: find::Options::default(),
+            tab_width: 4,
+            insert_spaces: true,
+            auto_indent: true,
+            auto_close_brackets: true,
+            save: SaveOptions::default(),
+            keys: BTreeMap::new()
+        }
+    }
+}
+
+impl EditorConfig {
+    /// Reads the saved settings, falling back to the defaults when the file
+    /// is missing or can't be parsed.
+    pub fn load() -> Self {
+        let mut config = config_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str::<EditorConfig>(&json).ok())
+            .unwrap_or_default();
+
+        if !config.font_size.is_finite() {
+            config.font_size = DEFAULT_FONT_SIZE;
+        }
+        config.font_size = config.font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+
+        config.positions.retain(|(path, _)| path.exists());
+        config.positions.truncate(MAX_POSITIONS);
+
+        config
+    }
+
+    pub fn position(&self, path: &Path) -> Option<(usize, usize)> {
+        self.positions
+            .iter()
+            .find(|(known, _)| known == path)
+            .map(|&(_, position)| position)
+    }
+
+    pub fn set_position(&mut self, path: PathBuf, position: (usize, usize)) {
+        self.positions.retain(|(known, _)| *known != path);
+        self.positions.insert(0, (path, position));
+        self.positions.truncate(MAX_POSITIONS);
+    }
+
+    /// Writes the settings through a temp file and a rename, so a save cut
+    /// short never leaves a half-written file for `load` to give up on.
+    pub async fn save(self) -> Result<(), io::ErrorKind> {
+        let path = config_path().ok_or(io::ErrorKind::NotFound)?;
+
+        if let Some(directory) = path.parent() {
+            tokio::fs::create_dir_all(directory)
+            .await
+            .map_err(|error| error.kind())?;
+        }
+
+        let json = serde_json::to_string_pretty(&self).map_err(|_| io::ErrorKind::InvalidData)?;
+
+        file::write_atomically(&path, json.as_bytes())
+        .await
+        .map(|_| ())
+        .map_err(|error| match error {
+            Error::TempWriteFailed(kind) | Error::RenameFailed(kind) => kind,
+            _ => io::ErrorKind::Other
+        })
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("texteditor").join("settings.json"))
+}
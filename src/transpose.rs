@@ -0,0 +1,53 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Swaps the grapheme before `offset` with the one after it, the way
+/// emacs' `C-t` does, and returns the new text with the offset just past
+/// both. Nothing happens at either end of a line.
+pub fn chars(text: &str, offset: usize) -> Option<(String, usize)> {
+    let (before, after) = text.split_at(offset);
+
+    let previous = before.graphemes(true).next_back()?;
+    let next = after.graphemes(true).next()?;
+
+    if previous == "\n" || next == "\n" || previous == "\r\n" || next == "\r\n" {
+        return None;
+    }
+
+    let start = offset - previous.len();
+    let end = offset + next.len();
+
+    Some((format!("{}{next}{previous}{}", &text[..start], &text[end..]), end))
+}
+
+/// Swaps the word under `offset` with the word after it. Whatever lies
+/// between them stays put, and the offset ends up after the second word.
+pub fn words(text: &str, offset: usize) -> Option<(String, usize)> {
+    let is_word = |segment: &str| segment.chars().any(char::is_alphanumeric);
+
+    let segments = text.split_word_bound_indices().collect::<Vec<_>>();
+
+    // Right after a word counts as being on it.
+    let current = segments
+        .iter()
+        .position(|&(start, segment)| is_word(segment) && start <= offset && offset <= start + segment.len())?;
+
+    let next = segments[current + 1..]
+        .iter()
+        .position(|&(_, segment)| is_word(segment))
+        .map(|index| current + 1 + index)?;
+
+    let (first_start, first) = segments[current];
+    let (second_start, second) = segments[next];
+
+    let first_end = first_start + first.len();
+    let second_end = second_start + second.len();
+
+    let text = format!(
+        "{}{second}{}{first}{}",
+        &text[..first_start],
+        &text[first_end..second_start],
+        &text[second_end..]
+    );
+
+    Some((text, second_end))
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, StreamExt};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Reports every time something else writes, replaces or removes the file
+/// at `path`. The subscription is keyed by the path, so each open file keeps
+/// its own watcher and closing the file drops it.
+pub fn changes(path: PathBuf) -> iced::Subscription<PathBuf> {
+    iced::subscription::channel(path.clone(), 16, |mut output| async move {
+        let (sender, mut events) = mpsc::unbounded();
+
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                let _ = sender.unbounded_send(event);
+            }
+        });
+
+        // Editors and version control often save by renaming a new file
+        // over the old one, which a watch on the file itself would lose
+        // track of, so the whole directory is watched instead.
+        let watched = watcher.and_then(|mut watcher| {
+            let directory = path.parent().unwrap_or(&path).to_owned();
+            watcher.watch(&directory, RecursiveMode::NonRecursive).map(|_| watcher)
+        });
+
+        let Ok(_watcher) = watched else { return iced::futures::future::pending().await };
+
+        loop {
+            let Some(event) = events.next().await else { return iced::futures::future::pending().await };
+
+            let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+                && event.paths.iter().any(|changed| *changed == path);
+
+            if relevant {
+                let _ = output.send(path.clone()).await;
+            }
+        }
+    })
+}